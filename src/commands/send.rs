@@ -2,20 +2,34 @@
 //!
 //! This module implements the "send" command for sending e-books to Kindle devices.
 
-use log::{error, info};
+use log::{error, info, warn};
 
-use crate::models::KindleError;
-use crate::services::{AzureService, ConfigService, KindleService, SendService};
+use crate::models::{AuthFlow, Config, KindleError, TransportKind};
+use crate::services::{
+    AzureService, ConfigService, EmlTransport, GmailTransport, GoogleService, GraphTransport,
+    HttpService, KindleService, MailTransport, RetryService, SendService, SendmailTransport,
+    SmtpTransport,
+};
 
 /// Execute the send command
 ///
 /// This function reads the configuration, initializes the required services,
 /// and sends e-book files to the configured Kindle devices.
 ///
+/// # Arguments
+///
+/// * `jobs` - Number of emails sent concurrently, overriding the configuration
+/// * `device_code` - Whether to sign in to Azure with the device code flow
+/// * `no_browser` - Whether to only print the sign-in URL instead of opening it
+///
 /// # Returns
 ///
 /// * `Result<(), KindleError>` - Success or an error
-pub async fn execute_send_command() -> Result<(), KindleError> {
+pub async fn execute_send_command(
+    jobs: Option<usize>,
+    device_code: bool,
+    no_browser: bool,
+) -> Result<(), KindleError> {
     // Read the configuration
     let config_result = ConfigService::read_config();
 
@@ -24,24 +38,94 @@ pub async fn execute_send_command() -> Result<(), KindleError> {
         return Err(e);
     }
 
-    let config = config_result.unwrap();
+    let mut config = config_result.unwrap();
 
-    // Initialize AzureService
-    let azure_service = AzureService::new(
-        &config.azure.client_id,
-        &config.azure.client_secret,
-        &config.azure.tenant_id,
-        &config.callback_uri,
-    );
+    // Apply the command-line overrides
+    if let Some(jobs) = jobs {
+        config.jobs = jobs;
+    }
+    if no_browser {
+        config.login.open_browser = false;
+    }
+    if device_code {
+        match config.azure.as_mut() {
+            Some(azure) if config.transport == TransportKind::Graph => {
+                azure.auth_flow = AuthFlow::DeviceCode;
+            }
+            _ => warn!("The device code flow is only available with the graph transport"),
+        }
+    }
 
-    // Initialize KindleService
-    let kindle_service = KindleService::new(&config.receivers);
+    // Initialize the configured transport and send files
+    let result = match config.transport {
+        TransportKind::Graph => {
+            let azure = config
+                .azure
+                .as_ref()
+                .ok_or_else(|| missing_section_error("graph", "azure"))?;
 
-    // Initialize SendService
-    let send_service = SendService::new(azure_service, kindle_service, &config);
+            let client = HttpService::build_client(&config.http)?;
+            let retry_service = RetryService::new(&config.retry);
+            let azure_service = AzureService::new(
+                azure,
+                &config.callback_uri,
+                &config.login,
+                &client,
+                retry_service,
+            );
 
-    // Send files
-    let result = send_service.send_files().await;
+            send_with_transport(
+                GraphTransport::new(azure_service, &client, retry_service),
+                &config,
+            )
+            .await
+        }
+        TransportKind::Smtp => {
+            let smtp = config
+                .smtp
+                .as_ref()
+                .ok_or_else(|| missing_section_error("smtp", "smtp"))?;
+
+            send_with_transport(SmtpTransport::new(smtp), &config).await
+        }
+        TransportKind::Gmail => {
+            let google = config
+                .google
+                .as_ref()
+                .ok_or_else(|| missing_section_error("gmail", "google"))?;
+
+            let client = HttpService::build_client(&config.http)?;
+            let google_service = GoogleService::new(
+                &google.client_id,
+                &google.client_secret,
+                &config.callback_uri,
+                &config.login,
+                &client,
+            );
+
+            send_with_transport(
+                GmailTransport::new(google_service, google, &client),
+                &config,
+            )
+            .await
+        }
+        TransportKind::Eml => {
+            let eml = config
+                .eml
+                .as_ref()
+                .ok_or_else(|| missing_section_error("eml", "eml"))?;
+
+            send_with_transport(EmlTransport::new(eml), &config).await
+        }
+        TransportKind::Sendmail => {
+            let sendmail = config
+                .sendmail
+                .as_ref()
+                .ok_or_else(|| missing_section_error("sendmail", "sendmail"))?;
+
+            send_with_transport(SendmailTransport::new(sendmail), &config).await
+        }
+    };
 
     match result {
         Ok(_) => {
@@ -54,3 +138,48 @@ pub async fn execute_send_command() -> Result<(), KindleError> {
         }
     }
 }
+
+/// Send the e-book files with the given transport
+///
+/// # Arguments
+///
+/// * `transport` - The mail transport delivering the emails
+/// * `config` - The application configuration
+///
+/// # Returns
+///
+/// * `Result<(), KindleError>` - Success or an error
+async fn send_with_transport<T: MailTransport>(
+    transport: T,
+    config: &Config,
+) -> Result<(), KindleError> {
+    // Initialize KindleService
+    let kindle_service = KindleService::new(&config.receivers, &config.message);
+
+    // Initialize SendService
+    let mut send_service = SendService::new(transport, kindle_service, config);
+
+    // Send files
+    send_service.send_files().await
+}
+
+/// Build the error reported when a transport's configuration section is missing
+///
+/// # Arguments
+///
+/// * `transport` - Name of the selected transport
+/// * `section` - Name of the missing configuration section
+///
+/// # Returns
+///
+/// * `KindleError` - The configuration error
+fn missing_section_error(transport: &str, section: &str) -> KindleError {
+    let e = KindleError {
+        message: format!(
+            "The {} transport requires a \"{}\" section in the configuration",
+            transport, section
+        ),
+    };
+    error!("Error reading configuration: {}", e.message);
+    e
+}
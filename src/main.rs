@@ -25,7 +25,17 @@ struct Cli {
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// Send e-book files to the configured Kindle device
-    Send {},
+    Send {
+        /// Number of emails sent concurrently (overrides the configuration)
+        #[arg(short, long)]
+        jobs: Option<usize>,
+        /// Sign in to Azure with the device code flow (overrides the configuration)
+        #[arg(long)]
+        device_code: bool,
+        /// Print the sign-in URL without opening the browser (overrides the configuration)
+        #[arg(long)]
+        no_browser: bool,
+    },
 }
 
 /// Main entry point for the Kindle-Sender application
@@ -43,8 +53,12 @@ async fn main() {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Send {} => {
-            if let Err(e) = commands::execute_send_command().await {
+        Commands::Send {
+            jobs,
+            device_code,
+            no_browser,
+        } => {
+            if let Err(e) = commands::execute_send_command(*jobs, *device_code, *no_browser).await {
                 error!("Command failed: {}", e);
                 std::process::exit(1);
             }
@@ -20,6 +20,39 @@ pub struct TokenResponse {
     pub token_type: String,
     /// Optional timestamp when the token will expire (stored locally)
     pub expires_at: Option<i64>,
+    /// Space-separated scopes granted to the token
+    #[serde(default)]
+    pub scope: Option<String>,
+}
+
+/// Response structure from the OAuth device authorization endpoint
+#[derive(Debug, Deserialize)]
+pub struct DeviceCodeResponse {
+    /// Code identifying the sign-in on the token endpoint
+    pub device_code: String,
+    /// Code the user enters on the verification page
+    pub user_code: String,
+    /// URL of the verification page
+    pub verification_uri: String,
+    /// Number of seconds until the codes expire
+    pub expires_in: u64,
+    /// Minimum number of seconds between two token requests
+    #[serde(default = "default_polling_interval")]
+    pub interval: u64,
+}
+
+/// Error response structure from the OAuth token endpoint
+#[derive(Debug, Deserialize)]
+pub struct TokenErrorResponse {
+    /// Error code (e.g. "authorization_pending")
+    pub error: String,
+    /// Human-readable description of the error
+    pub error_description: Option<String>,
+}
+
+/// Default polling interval of the device code flow in seconds, per RFC 8628
+fn default_polling_interval() -> u64 {
+    5
 }
 
 impl TokenResponse {
@@ -35,4 +68,31 @@ impl TokenResponse {
         }
         false
     }
+
+    /// Check if the token was granted the given permissions
+    ///
+    /// Tokens cached without their scopes are assumed to have them.
+    ///
+    /// # Arguments
+    ///
+    /// * `permissions` - The permission names (e.g. "Mail.Send")
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if every permission was granted, false otherwise
+    pub fn has_permissions(&self, permissions: &[&str]) -> bool {
+        let Some(scope) = &self.scope else {
+            return true;
+        };
+
+        // Granted scopes may be qualified with the resource (e.g. "https://graph.microsoft.com/Mail.Send")
+        permissions.iter().all(|permission| {
+            scope.split_whitespace().any(|granted| {
+                granted
+                    .rsplit('/')
+                    .next()
+                    .is_some_and(|name| name.eq_ignore_ascii_case(permission))
+            })
+        })
+    }
 }
@@ -3,6 +3,7 @@
 //! This module defines the configuration data structures for the application.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
@@ -15,15 +16,71 @@ use std::path::Path;
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     /// URI for OAuth callback endpoint
+    #[serde(default = "default_callback_uri")]
     pub callback_uri: String,
+    /// Behavior of the browser sign-in
+    #[serde(default)]
+    pub login: LoginConfig,
     /// Directory path where new e-books to be sent are located
     pub ebook_to_send_directory: String,
     /// Directory path where e-books are moved after being sent
     pub ebook_sent_directory: String,
     /// List of email addresses to send e-books to (Kindle addresses)
     pub receivers: Vec<String>,
-    /// Azure API configuration
-    pub azure: AzureConfig,
+    /// Transport used to deliver the emails
+    #[serde(default)]
+    pub transport: TransportKind,
+    /// Azure API configuration, required by the Graph transport
+    pub azure: Option<AzureConfig>,
+    /// SMTP server configuration, required by the SMTP transport
+    pub smtp: Option<SmtpConfig>,
+    /// Google API configuration, required by the Gmail transport
+    pub google: Option<GoogleConfig>,
+    /// EML output configuration, required by the EML transport
+    pub eml: Option<EmlConfig>,
+    /// Sendmail command configuration, required by the sendmail transport
+    pub sendmail: Option<SendmailConfig>,
+    /// Retry policy for Microsoft Graph and token requests
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// HTTP client settings shared by the API requests
+    #[serde(default)]
+    pub http: HttpConfig,
+    /// Bundling of several e-books into one email
+    #[serde(default)]
+    pub batch: BatchConfig,
+    /// Number of emails sent concurrently
+    #[serde(default = "default_jobs")]
+    pub jobs: usize,
+    /// Whether the receivers share one email or each get their own
+    #[serde(default)]
+    pub delivery: DeliveryMode,
+    /// Templates of the subject and body of the emails
+    #[serde(default)]
+    pub message: MessageConfig,
+    /// Check of the replies of Amazon after sending
+    #[serde(default)]
+    pub reply_check: ReplyCheckConfig,
+    /// Limits on the emails sent, shared by the successive runs
+    #[serde(default)]
+    pub quota: QuotaConfig,
+}
+
+/// Mail transports available to deliver e-books
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportKind {
+    /// Microsoft Graph API (`sendMail`)
+    #[default]
+    Graph,
+    /// SMTP server
+    Smtp,
+    /// Gmail API (`users.messages.send`)
+    Gmail,
+    /// `.eml` files written to a directory, nothing is sent
+    Eml,
+    /// Local sendmail-compatible command
+    Sendmail,
 }
 
 /// Azure API configuration parameters
@@ -31,10 +88,551 @@ pub struct Config {
 pub struct AzureConfig {
     /// Azure application client ID
     pub client_id: String,
-    /// Azure application client secret
-    pub client_secret: String,
+    /// Azure application client secret, omitted when the application is registered
+    /// as a public client
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    /// Certificate authenticating the application in the client credentials flow,
+    /// preferred over the client secret
+    pub client_certificate: Option<ClientCertificateConfig>,
     /// Azure tenant ID (often "common" for multi-tenant applications)
     pub tenant_id: String,
+    /// Cloud hosting the tenant, which determines the default endpoints
+    #[serde(default)]
+    pub cloud: AzureCloud,
+    /// Base URL of the Microsoft Graph API, overriding the cloud default (e.g. a mock server)
+    pub graph_base_url: Option<String>,
+    /// Base URL of the login authority, overriding the cloud default
+    pub authority_host: Option<String>,
+    /// OAuth flow used to sign in
+    #[serde(default)]
+    pub auth_flow: AuthFlow,
+    /// Mailbox sending the emails (user ID or principal name), the signed-in user's when unset
+    pub send_as: Option<String>,
+    /// Address shown as the sender of the emails, e.g. a shared mailbox or an alias
+    pub from: Option<String>,
+    /// Addresses replies are sent to
+    #[serde(default)]
+    pub reply_to: Vec<String>,
+    /// Whether a copy of the emails is kept in the Sent Items folder
+    #[serde(default = "default_save_to_sent_items")]
+    pub save_to_sent_items: bool,
+    /// What to do with the copy of the emails kept in the Sent Items folder
+    #[serde(default)]
+    pub sent_items_cleanup: SentItemsCleanup,
+    /// Name of the mail folder the sent emails are moved to by the `move` cleanup
+    #[serde(default = "default_sent_items_folder")]
+    pub sent_items_folder: String,
+}
+
+/// Parameters of the browser sign-in
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoginConfig {
+    /// Whether the sign-in page is opened in the system browser
+    pub open_browser: bool,
+    /// Longest wait for the sign-in to complete, in seconds
+    pub timeout_secs: u64,
+}
+
+impl Default for LoginConfig {
+    fn default() -> Self {
+        LoginConfig {
+            open_browser: true,
+            timeout_secs: 300,
+        }
+    }
+}
+
+/// OAuth flows available to sign in to Azure
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthFlow {
+    /// Sign in with a browser redirected to the local callback server
+    #[default]
+    AuthorizationCode,
+    /// Sign in on any device with a code displayed by the tool
+    DeviceCode,
+    /// Authenticate as the application itself, without any user interaction
+    ClientCredentials,
+}
+
+/// Certificate authenticating the application with a signed client assertion
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClientCertificateConfig {
+    /// PEM file of the certificate uploaded to the application registration
+    pub certificate_path: String,
+    /// PEM file of the RSA private key of the certificate (PKCS#8 or PKCS#1)
+    pub private_key_path: String,
+}
+
+/// Cleanup of the sent emails once they are delivered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SentItemsCleanup {
+    /// The emails stay in the Sent Items folder
+    #[default]
+    None,
+    /// The emails are permanently deleted
+    Delete,
+    /// The emails are moved to a dedicated mail folder
+    Move,
+}
+
+/// Microsoft clouds with their own Graph and login endpoints
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AzureCloud {
+    /// Global Azure cloud
+    #[default]
+    Global,
+    /// Azure US Government (L4)
+    UsGovernment,
+    /// Azure US Government DoD (L5)
+    UsGovernmentDod,
+    /// Azure China operated by 21Vianet
+    China,
+}
+
+impl AzureCloud {
+    /// Get the default Microsoft Graph URL of the cloud
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The Graph URL, also used as the resource of the OAuth scopes
+    pub fn graph_url(&self) -> &'static str {
+        match self {
+            AzureCloud::Global => "https://graph.microsoft.com",
+            AzureCloud::UsGovernment => "https://graph.microsoft.us",
+            AzureCloud::UsGovernmentDod => "https://dod-graph.microsoft.us",
+            AzureCloud::China => "https://microsoftgraph.chinacloudapi.cn",
+        }
+    }
+
+    /// Get the default login authority host of the cloud
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The authority host URL
+    pub fn authority_host(&self) -> &'static str {
+        match self {
+            AzureCloud::Global => "https://login.microsoftonline.com",
+            AzureCloud::UsGovernment | AzureCloud::UsGovernmentDod => {
+                "https://login.microsoftonline.us"
+            }
+            AzureCloud::China => "https://login.chinacloudapi.cn",
+        }
+    }
+}
+
+impl AzureConfig {
+    /// Get the client secret sent with the token requests
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&str>` - The secret, or None for a public client
+    pub fn client_secret(&self) -> Option<&str> {
+        self.client_secret
+            .as_deref()
+            .filter(|secret| !secret.is_empty())
+    }
+
+    /// Get the Microsoft Graph base URL, without the API version
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The configured URL or the default URL of the cloud
+    pub fn graph_endpoint(&self) -> String {
+        self.graph_base_url
+            .as_deref()
+            .unwrap_or(self.cloud.graph_url())
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Get the OAuth endpoint of the tenant
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The authority URL including the tenant, without the trailing `/oauth2/v2.0`
+    pub fn authority_endpoint(&self) -> String {
+        format!(
+            "{}/{}",
+            self.authority_host
+                .as_deref()
+                .unwrap_or(self.cloud.authority_host())
+                .trim_end_matches('/'),
+            self.tenant_id
+        )
+    }
+
+    /// Get the Microsoft Graph path of the mailbox sending the emails
+    ///
+    /// # Returns
+    ///
+    /// * `String` - `/users/{id}` for a configured mailbox, `/me` otherwise
+    pub fn mailbox_path(&self) -> String {
+        match &self.send_as {
+            Some(mailbox) => format!("/users/{}", encode_path_segment(mailbox)),
+            None => "/me".to_string(),
+        }
+    }
+
+    /// Get the Microsoft Graph permissions needed to send the emails
+    ///
+    /// Sending from another mailbox than the signed-in user's requires the shared variants.
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<&'static str>` - The permission names
+    pub fn graph_permissions(&self) -> Vec<&'static str> {
+        // Drafts, used for large attachments and the cleanup, require Mail.ReadWrite
+        let mut permissions = vec!["Mail.Send", "Mail.ReadWrite"];
+        if self.send_as.is_some() || self.from.is_some() {
+            permissions.extend(["Mail.Send.Shared", "Mail.ReadWrite.Shared"]);
+        }
+        permissions
+    }
+
+    /// Get a Microsoft Graph scope qualified with the resource of the cloud
+    ///
+    /// # Arguments
+    ///
+    /// * `permission` - The permission name (e.g. "Mail.Send" or ".default")
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The fully qualified scope
+    pub fn graph_scope(&self, permission: &str) -> String {
+        format!("{}/{}", self.cloud.graph_url(), permission)
+    }
+}
+
+/// Google API configuration parameters
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GoogleConfig {
+    /// Google OAuth client ID
+    pub client_id: String,
+    /// Google OAuth client secret
+    pub client_secret: String,
+    /// Gmail address the emails are sent from (must be an approved Kindle sender)
+    pub from: String,
+}
+
+/// EML output configuration parameters
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EmlConfig {
+    /// Directory where the `.eml` files are written
+    pub directory: String,
+    /// Sender address written in the messages
+    pub from: Option<String>,
+}
+
+/// Sendmail command configuration parameters
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendmailConfig {
+    /// Sendmail-compatible command receiving the message on its standard input
+    #[serde(default = "default_sendmail_command")]
+    pub command: String,
+    /// Arguments of the command, recipients are read from the message headers by default
+    #[serde(default = "default_sendmail_args")]
+    pub args: Vec<String>,
+    /// Sender address of the emails (must be an approved Kindle sender)
+    pub from: String,
+}
+
+/// SMTP server configuration parameters
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    /// Hostname of the SMTP server
+    pub host: String,
+    /// Port of the SMTP server, defaults to the standard port of the security mode
+    pub port: Option<u16>,
+    /// Connection security mode
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// Username used to authenticate, authentication is skipped when missing
+    pub username: Option<String>,
+    /// Password used to authenticate
+    pub password: Option<String>,
+    /// Allowed authentication mechanisms, PLAIN and LOGIN when empty
+    #[serde(default)]
+    pub auth_mechanisms: Vec<SmtpAuthMechanism>,
+    /// Sender address of the emails (must be an approved Kindle sender)
+    pub from: String,
+}
+
+/// Connection security modes for SMTP
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Plain text connection, only suitable for local servers (default port 25)
+    None,
+    /// Plain text connection upgraded with STARTTLS (default port 587)
+    #[default]
+    StartTls,
+    /// Implicit TLS connection (default port 465)
+    Tls,
+}
+
+/// SMTP authentication mechanisms
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpAuthMechanism {
+    /// AUTH PLAIN
+    Plain,
+    /// AUTH LOGIN
+    Login,
+}
+
+/// Templates of the subject and body of the emails
+///
+/// The templates may contain the placeholders `{filename}`, `{stem}`, `{ext}`,
+/// `{size}`, `{date}`, `{title}` and `{author}`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageConfig {
+    /// Template of the subject
+    pub subject: String,
+    /// Template of the plain text body
+    pub body: String,
+    /// Templates replacing the default ones for some file extensions (e.g. `pdf`)
+    pub overrides: BTreeMap<String, MessageOverride>,
+}
+
+impl Default for MessageConfig {
+    fn default() -> Self {
+        MessageConfig {
+            subject: "Your Kindle File".to_string(),
+            body: "".to_string(),
+            overrides: BTreeMap::new(),
+        }
+    }
+}
+
+impl MessageConfig {
+    /// Get the key of the override applying to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Name of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Option<&str>` - The key of the override of the extension of the file, or None
+    ///   if the default templates apply
+    pub fn override_key(&self, filename: &str) -> Option<&str> {
+        let extension = Path::new(filename).extension()?.to_string_lossy();
+        self.overrides
+            .keys()
+            .find(|key| key.trim_start_matches('.').eq_ignore_ascii_case(&extension))
+            .map(String::as_str)
+    }
+}
+
+/// Templates replacing the default ones for a file extension
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MessageOverride {
+    /// Template of the subject, the default one is used when unset
+    pub subject: Option<String>,
+    /// Template of the plain text body, the default one is used when unset
+    pub body: Option<String>,
+}
+
+/// Ways of addressing the emails to the receivers
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMode {
+    /// One email addressed to every receiver
+    #[default]
+    Combined,
+    /// One email per receiver, with the outcome recorded for each of them
+    PerRecipient,
+}
+
+/// Retry policy parameters for HTTP requests
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Maximum number of attempts for a request, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds, doubled after each attempt
+    pub initial_delay_ms: u64,
+    /// Maximum delay between two attempts in seconds, also caps `Retry-After`
+    pub max_delay_secs: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_attempts: 5,
+            initial_delay_ms: 500,
+            max_delay_secs: 60,
+        }
+    }
+}
+
+/// HTTP client settings (timeouts, proxy and certificates)
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Maximum time to establish a connection in seconds
+    pub connect_timeout_secs: u64,
+    /// Maximum time to wait for data on an open connection in seconds
+    pub read_timeout_secs: u64,
+    /// URL of the HTTP(S) proxy, the system proxy settings are used when unset
+    pub proxy: Option<String>,
+    /// Hosts and domains reached without the proxy
+    pub no_proxy: Vec<String>,
+    /// Path to a PEM bundle of extra root certificates to trust
+    pub ca_bundle: Option<String>,
+    /// User agent sent with the requests
+    pub user_agent: String,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 60,
+            proxy: None,
+            no_proxy: Vec::new(),
+            ca_bundle: None,
+            user_agent: format!("kindle-sender/{}", env!("CARGO_PKG_VERSION")),
+        }
+    }
+}
+
+/// Parameters of the check of the replies of Amazon
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplyCheckConfig {
+    /// Whether the inbox is checked for replies of Amazon after sending
+    pub enabled: bool,
+    /// Time spent waiting for the replies after sending, in seconds
+    pub wait_secs: u64,
+    /// Delay between two checks of the inbox, in seconds
+    pub poll_interval_secs: u64,
+    /// Age of the oldest replies read from the inbox, in hours
+    pub lookback_hours: u64,
+    /// Directory where files rejected by Amazon are moved, `rejected` in the sent
+    /// directory by default
+    pub rejected_directory: Option<String>,
+}
+
+impl Default for ReplyCheckConfig {
+    fn default() -> Self {
+        ReplyCheckConfig {
+            enabled: false,
+            wait_secs: 120,
+            poll_interval_secs: 20,
+            lookback_hours: 48,
+            rejected_directory: None,
+        }
+    }
+}
+
+/// Limits on the emails sent, as enforced by mail providers
+///
+/// Limits that are not set are not enforced.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QuotaConfig {
+    /// Maximum number of emails sent in 24 hours
+    pub max_messages_per_day: Option<u32>,
+    /// Maximum number of recipients of the emails sent in 24 hours
+    pub max_recipients_per_day: Option<u32>,
+    /// Sustained number of emails sent per minute
+    pub messages_per_minute: Option<f64>,
+    /// Number of emails that can be sent at once before the rate applies
+    pub burst: u32,
+}
+
+impl Default for QuotaConfig {
+    fn default() -> Self {
+        QuotaConfig {
+            max_messages_per_day: None,
+            max_recipients_per_day: None,
+            messages_per_minute: None,
+            burst: 5,
+        }
+    }
+}
+
+/// Parameters for bundling several e-books into one email
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    /// Whether several e-books are sent in the same email
+    pub enabled: bool,
+    /// Maximum total size of the attachments of an email in megabytes (Amazon accepts
+    /// at most 50)
+    pub max_size_mb: u64,
+    /// Maximum number of attachments of an email (Amazon accepts at most 25)
+    pub max_attachments: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            enabled: false,
+            max_size_mb: 50,
+            max_attachments: 25,
+        }
+    }
+}
+
+/// Percent-encode a value used as a segment of a URL path
+///
+/// # Arguments
+///
+/// * `segment` - The value
+///
+/// # Returns
+///
+/// * `String` - The value with every byte but the unreserved characters and `@` encoded
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~@".contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{:02X}", byte)
+            }
+        })
+        .collect()
+}
+
+/// Default OAuth callback URI
+fn default_callback_uri() -> String {
+    "http://localhost:8080/callback".to_string()
+}
+
+/// Default choice of keeping the sent emails in the Sent Items folder
+fn default_save_to_sent_items() -> bool {
+    true
+}
+
+/// Default mail folder the sent emails are moved to
+fn default_sent_items_folder() -> String {
+    "Kindle".to_string()
+}
+
+/// Default number of emails sent concurrently
+fn default_jobs() -> usize {
+    1
+}
+
+/// Default sendmail-compatible command
+fn default_sendmail_command() -> String {
+    "/usr/sbin/sendmail".to_string()
+}
+
+/// Default sendmail arguments: read recipients from the headers, ignore lone dots
+fn default_sendmail_args() -> Vec<String> {
+    vec!["-t".to_string(), "-i".to_string()]
 }
 
 impl Config {
@@ -64,6 +662,8 @@ impl Config {
     pub fn display(&self) {
         println!("Configuration:");
         println!("  Callback URI: {}", self.callback_uri);
+        println!("  Transport: {:?}", self.transport);
+        println!("  Delivery: {:?}", self.delivery);
         println!(
             "  Ebook to send directory: {}",
             self.ebook_to_send_directory
@@ -75,3 +675,34 @@ impl Config {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn azure_config(send_as: Option<&str>) -> AzureConfig {
+        serde_json::from_value(serde_json::json!({
+            "client_id": "client",
+            "tenant_id": "tenant",
+            "send_as": send_as,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn mailbox_path_defaults_to_signed_in_user() {
+        assert_eq!(azure_config(None).mailbox_path(), "/me");
+    }
+
+    #[test]
+    fn mailbox_path_encodes_mailbox() {
+        assert_eq!(
+            azure_config(Some("books@example.com")).mailbox_path(),
+            "/users/books@example.com"
+        );
+        assert_eq!(
+            azure_config(Some("a#b?c/d%e f")).mailbox_path(),
+            "/users/a%23b%3Fc%2Fd%25e%20f"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+//! # Delivery Models
+//!
+//! This module defines the data structures recording which Kindle devices received
+//! which e-books, so that a failed delivery can be retried without duplicates.
+
+use std::collections::BTreeMap;
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::models::KindleError;
+
+/// Outcome of the delivery of files to each receiver, persisted between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DeliveryLog {
+    /// Delivery records of each receiver, by filename
+    #[serde(default)]
+    pub files: BTreeMap<String, BTreeMap<String, DeliveryRecord>>,
+}
+
+/// Outcome of the last delivery of a file to a receiver
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    /// Whether the file was delivered
+    pub status: DeliveryStatus,
+    /// Timestamp of the delivery attempt
+    pub timestamp: i64,
+    /// Error reported by the transport when the delivery failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Status of the delivery of a file to a receiver
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// The transport accepted the email
+    Delivered,
+    /// The transport failed to send the email
+    Failed,
+}
+
+impl DeliveryLog {
+    /// Check whether a file was delivered to a receiver
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Name of the file
+    /// * `receiver` - Email address of the receiver
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the file was delivered to the receiver, false otherwise
+    pub fn is_delivered(&self, filename: &str, receiver: &str) -> bool {
+        self.files
+            .get(filename)
+            .and_then(|records| records.get(receiver))
+            .is_some_and(|record| record.status == DeliveryStatus::Delivered)
+    }
+
+    /// Record the outcome of the delivery of a file to a receiver
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Name of the file
+    /// * `receiver` - Email address of the receiver
+    /// * `error` - The error that made the delivery fail, if any
+    pub fn record(&mut self, filename: &str, receiver: &str, error: Option<&KindleError>) {
+        let record = DeliveryRecord {
+            status: match error {
+                Some(_) => DeliveryStatus::Failed,
+                None => DeliveryStatus::Delivered,
+            },
+            timestamp: Utc::now().timestamp(),
+            error: error.map(|e| e.message.clone()),
+        };
+
+        self.files
+            .entry(filename.to_string())
+            .or_default()
+            .insert(receiver.to_string(), record);
+    }
+
+    /// Forget the deliveries of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Name of the file
+    pub fn remove_file(&mut self, filename: &str) {
+        self.files.remove(filename);
+    }
+}
@@ -16,3 +16,27 @@ impl std::fmt::Display for KindleError {
 }
 
 impl std::error::Error for KindleError {}
+
+impl KindleError {
+    /// Build an error from an unsuccessful HTTP response
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - Description of the failed operation
+    /// * `response` - The unsuccessful response
+    ///
+    /// # Returns
+    ///
+    /// * `KindleError` - Error containing the response status and body
+    pub async fn from_response(context: &str, response: reqwest::Response) -> Self {
+        let response_status = response.status();
+        match response.text().await {
+            Ok(message) => KindleError {
+                message: format!("{}: {:?} {:?}", context, response_status, message),
+            },
+            Err(e) => KindleError {
+                message: format!("Failed to read response: {}", e),
+            },
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! # Document Format Models
+//!
+//! This module defines the document formats accepted by the Send-to-Kindle service.
+
+/// Document format accepted by the Send-to-Kindle service
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentFormat {
+    /// EPUB e-book
+    Epub,
+    /// Portable Document Format
+    Pdf,
+    /// Microsoft Word (Office Open XML)
+    Docx,
+    /// Microsoft Word (legacy binary format)
+    Doc,
+    /// Rich Text Format
+    Rtf,
+    /// Plain text
+    Txt,
+    /// HTML page
+    Html,
+    /// JPEG image
+    Jpeg,
+    /// PNG image
+    Png,
+    /// GIF image
+    Gif,
+    /// BMP image
+    Bmp,
+    /// Mobipocket e-book
+    Mobi,
+    /// Kindle e-book (AZW, AZW3)
+    Azw,
+}
+
+impl DocumentFormat {
+    /// Get the format matching a file extension
+    ///
+    /// # Arguments
+    ///
+    /// * `extension` - The file extension, without the leading dot
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Self>` - The format, or None if the extension is not supported
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "epub" => Some(DocumentFormat::Epub),
+            "pdf" => Some(DocumentFormat::Pdf),
+            "docx" => Some(DocumentFormat::Docx),
+            "doc" => Some(DocumentFormat::Doc),
+            "rtf" => Some(DocumentFormat::Rtf),
+            "txt" => Some(DocumentFormat::Txt),
+            "html" | "htm" => Some(DocumentFormat::Html),
+            "jpg" | "jpeg" => Some(DocumentFormat::Jpeg),
+            "png" => Some(DocumentFormat::Png),
+            "gif" => Some(DocumentFormat::Gif),
+            "bmp" => Some(DocumentFormat::Bmp),
+            "mobi" | "prc" => Some(DocumentFormat::Mobi),
+            "azw" | "azw3" => Some(DocumentFormat::Azw),
+            _ => None,
+        }
+    }
+
+    /// Get the MIME type of the format
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The MIME type
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            DocumentFormat::Epub => "application/epub+zip",
+            DocumentFormat::Pdf => "application/pdf",
+            DocumentFormat::Docx => {
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document"
+            }
+            DocumentFormat::Doc => "application/msword",
+            DocumentFormat::Rtf => "application/rtf",
+            DocumentFormat::Txt => "text/plain",
+            DocumentFormat::Html => "text/html",
+            DocumentFormat::Jpeg => "image/jpeg",
+            DocumentFormat::Png => "image/png",
+            DocumentFormat::Gif => "image/gif",
+            DocumentFormat::Bmp => "image/bmp",
+            DocumentFormat::Mobi => "application/x-mobipocket-ebook",
+            DocumentFormat::Azw => "application/vnd.amazon.ebook",
+        }
+    }
+
+    /// Get the usual name of the format
+    ///
+    /// # Returns
+    ///
+    /// * `&'static str` - The name of the format
+    pub fn name(&self) -> &'static str {
+        match self {
+            DocumentFormat::Epub => "EPUB",
+            DocumentFormat::Pdf => "PDF",
+            DocumentFormat::Docx => "DOCX",
+            DocumentFormat::Doc => "DOC",
+            DocumentFormat::Rtf => "RTF",
+            DocumentFormat::Txt => "TXT",
+            DocumentFormat::Html => "HTML",
+            DocumentFormat::Jpeg => "JPEG",
+            DocumentFormat::Png => "PNG",
+            DocumentFormat::Gif => "GIF",
+            DocumentFormat::Bmp => "BMP",
+            DocumentFormat::Mobi => "MOBI",
+            DocumentFormat::Azw => "AZW",
+        }
+    }
+
+    /// Check whether a file named with this format's extension can hold content
+    /// detected as another format
+    ///
+    /// Mobipocket and Kindle e-books share the same container, and HTML files do not
+    /// always start with a recognizable tag, so they can be detected as plain text.
+    ///
+    /// # Arguments
+    ///
+    /// * `detected` - The format detected from the content of the file
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the content matches the format, false otherwise
+    pub fn matches(&self, detected: DocumentFormat) -> bool {
+        matches!(
+            (self, detected),
+            (DocumentFormat::Mobi, DocumentFormat::Azw)
+                | (DocumentFormat::Azw, DocumentFormat::Mobi)
+                | (DocumentFormat::Html, DocumentFormat::Txt)
+        ) || *self == detected
+    }
+}
@@ -0,0 +1,12 @@
+//! # Gmail Models
+//!
+//! This module defines data structures for sending emails with the Gmail API.
+
+use serde::Serialize;
+
+/// Structure representing a raw message sent with `users.messages.send`
+#[derive(Serialize)]
+pub struct GmailMessage {
+    /// RFC 822 message encoded in URL-safe base64
+    pub raw: String,
+}
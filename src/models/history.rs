@@ -0,0 +1,86 @@
+//! # Send History Models
+//!
+//! This module defines the history of the files sent to Kindle devices, which is used
+//! to match the replies of Amazon to the sends they are about.
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+/// Recent sends, persisted between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SendHistory {
+    /// The sends, oldest first
+    #[serde(default)]
+    pub records: Vec<SendRecord>,
+}
+
+/// Send of a file to Kindle devices
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendRecord {
+    /// Name of the file
+    pub filename: String,
+    /// Email addresses of the Kindle devices
+    pub recipients: Vec<String>,
+    /// Message-ID of the email carrying the file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+    /// SHA-256 hash of the file content, in hexadecimal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    /// Timestamp of the send
+    pub sent_at: i64,
+    /// Outcome of the send as reported by Amazon
+    pub status: SendStatus,
+    /// Link to follow to verify the send, when Amazon asks for it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification_link: Option<String>,
+}
+
+/// Outcome of a send as reported by Amazon
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendStatus {
+    /// The email was sent, Amazon did not report any problem
+    Sent,
+    /// Amazon could not deliver the document
+    Rejected,
+    /// Amazon waits for the sender to verify the request
+    PendingVerification,
+}
+
+impl SendHistory {
+    /// Record the send of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Name of the file
+    /// * `recipients` - Email addresses of the Kindle devices
+    /// * `message_id` - Message-ID of the email carrying the file
+    /// * `sha256` - SHA-256 hash of the file content
+    pub fn record_sent(
+        &mut self,
+        filename: &str,
+        recipients: &[String],
+        message_id: &str,
+        sha256: &str,
+    ) {
+        self.records.push(SendRecord {
+            filename: filename.to_string(),
+            recipients: recipients.to_vec(),
+            message_id: Some(message_id.to_string()),
+            sha256: Some(sha256.to_string()),
+            sent_at: Utc::now().timestamp(),
+            status: SendStatus::Sent,
+            verification_link: None,
+        });
+    }
+
+    /// Forget the sends older than a given time
+    ///
+    /// # Arguments
+    ///
+    /// * `before` - Timestamp of the oldest send to keep
+    pub fn prune(&mut self, before: i64) {
+        self.records.retain(|record| record.sent_at >= before);
+    }
+}
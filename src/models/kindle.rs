@@ -2,7 +2,7 @@
 //!
 //! This module defines data structures for sending emails to Kindle devices.
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// Structure representing an email to be sent via the Graph API
 #[derive(Serialize)]
@@ -24,12 +24,37 @@ pub struct Message {
     /// List of email recipients
     #[serde(rename = "toRecipients")]
     pub to_recipients: Vec<Recipient>,
+    /// Sender shown to the recipients, the sending mailbox when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<Recipient>,
+    /// Addresses replies are sent to
+    #[serde(rename = "replyTo", skip_serializing_if = "Vec::is_empty")]
+    pub reply_to: Vec<Recipient>,
+    /// Message-ID of the email
+    #[serde(rename = "internetMessageId")]
+    pub internet_message_id: String,
+    /// Custom headers of the email
+    #[serde(
+        rename = "internetMessageHeaders",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub internet_message_headers: Vec<InternetMessageHeader>,
     /// List of file attachments
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub attachments: Vec<Attachment>,
 }
 
-/// Structure representing an email body
+/// Structure representing a custom email header
 #[derive(Serialize)]
+pub struct InternetMessageHeader {
+    /// Name of the header, starting with "X-"
+    pub name: String,
+    /// Value of the header
+    pub value: String,
+}
+
+/// Structure representing an email body
+#[derive(Serialize, Deserialize)]
 pub struct Body {
     /// Content type (e.g., "Text", "HTML")
     #[serde(rename = "contentType")]
@@ -39,7 +64,7 @@ pub struct Body {
 }
 
 /// Structure representing an email recipient
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Recipient {
     /// Email address of the recipient
     #[serde(rename = "emailAddress")]
@@ -47,7 +72,7 @@ pub struct Recipient {
 }
 
 /// Structure representing an email address
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct EmailAddress {
     /// The email address string
     pub address: String,
@@ -68,3 +93,103 @@ pub struct Attachment {
     #[serde(rename = "contentBytes")]
     pub content_bytes: String,
 }
+
+/// Structure representing the request body used to create an attachment upload session
+#[derive(Serialize)]
+pub struct UploadSessionRequest {
+    /// Description of the attachment to be uploaded
+    #[serde(rename = "AttachmentItem")]
+    pub attachment_item: AttachmentItem,
+}
+
+/// Structure describing an attachment uploaded through an upload session
+#[derive(Serialize)]
+pub struct AttachmentItem {
+    /// Type of the attachment, always "file" for e-books
+    #[serde(rename = "attachmentType")]
+    pub attachment_type: String,
+    /// Filename of the attachment
+    pub name: String,
+    /// Size of the attachment in bytes
+    pub size: u64,
+    /// MIME type of the attachment
+    #[serde(rename = "contentType")]
+    pub content_type: String,
+}
+
+/// Structure representing an upload session returned by the Graph API
+#[derive(Deserialize)]
+pub struct UploadSession {
+    /// Pre-authenticated URL that receives the attachment chunks
+    #[serde(rename = "uploadUrl")]
+    pub upload_url: String,
+}
+
+/// Structure representing a draft message created through the Graph API
+#[derive(Deserialize)]
+pub struct DraftMessage {
+    /// Identifier of the draft message
+    pub id: String,
+}
+
+/// Structure representing the location of a message returned by the Graph API
+#[derive(Deserialize)]
+pub struct MessageLocation {
+    /// Identifier of the mail folder containing the message
+    #[serde(rename = "parentFolderId")]
+    pub parent_folder_id: String,
+}
+
+/// Structure representing a mail folder returned by the Graph API
+#[derive(Deserialize)]
+pub struct MailFolder {
+    /// Identifier of the mail folder
+    pub id: String,
+}
+
+/// Structure representing a list of mail folders returned by the Graph API
+#[derive(Deserialize)]
+pub struct MailFolderList {
+    /// The mail folders
+    pub value: Vec<MailFolder>,
+}
+
+/// Structure representing the request body used to create a mail folder
+#[derive(Serialize)]
+pub struct MailFolderRequest {
+    /// Name of the mail folder
+    #[serde(rename = "displayName")]
+    pub display_name: String,
+}
+
+/// Structure representing the request body used to move a message
+#[derive(Serialize)]
+pub struct MoveRequest {
+    /// Identifier of the destination mail folder
+    #[serde(rename = "destinationId")]
+    pub destination_id: String,
+}
+
+/// Structure representing a received message returned by the Graph API
+#[derive(Deserialize)]
+pub struct InboxMessage {
+    /// Email subject line
+    pub subject: Option<String>,
+    /// Email body content
+    pub body: Option<Body>,
+    /// Sender of the message
+    pub from: Option<Recipient>,
+    /// Date and time the message was received (ISO 8601)
+    #[serde(rename = "receivedDateTime")]
+    pub received_date_time: String,
+}
+
+/// Structure representing a list of received messages returned by the Graph API
+#[derive(Deserialize)]
+pub struct InboxMessageList {
+    /// The messages
+    pub value: Vec<InboxMessage>,
+    /// URL of the next page of messages, absent on the last page
+    #[serde(rename = "@odata.nextLink")]
+    pub next_link: Option<String>,
+}
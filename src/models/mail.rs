@@ -0,0 +1,65 @@
+//! # Mail Models
+//!
+//! This module defines transport-independent data structures describing the emails
+//! sent to Kindle devices.
+
+use crate::models::DocumentFormat;
+
+/// Structure representing an email to be delivered by a mail transport
+pub struct OutgoingMail {
+    /// Email addresses of the recipients (Kindle addresses)
+    pub recipients: Vec<String>,
+    /// Email subject line
+    pub subject: String,
+    /// Plain text body of the email
+    pub body: String,
+    /// Files attached to the email
+    pub attachments: Vec<MailAttachment>,
+    /// Message-ID of the email, with its angle brackets
+    pub message_id: String,
+    /// Custom headers tying the email to the run and files that produced it
+    pub headers: Vec<(String, String)>,
+}
+
+/// Structure representing an email received by the sender
+pub struct IncomingMail {
+    /// Email address of the sender
+    pub from: String,
+    /// Email subject line
+    pub subject: String,
+    /// Plain text body of the email
+    pub body: String,
+    /// Timestamp of the reception of the email
+    pub received_at: i64,
+}
+
+/// Structure representing a file attached to an outgoing email
+#[derive(Clone)]
+pub struct MailAttachment {
+    /// Path of the file on disk
+    pub path: String,
+    /// Filename of the attachment
+    pub name: String,
+    /// MIME type of the attachment
+    pub content_type: String,
+    /// Format of the file, None if it is unknown
+    pub format: Option<DocumentFormat>,
+    /// Size of the file in bytes
+    pub size: u64,
+    /// SHA-256 hash of the file content, in hexadecimal
+    pub sha256: String,
+}
+
+impl OutgoingMail {
+    /// Total size of the attachments in bytes
+    ///
+    /// # Returns
+    ///
+    /// * `u64` - Sum of the attachment sizes
+    pub fn attachments_size(&self) -> u64 {
+        self.attachments
+            .iter()
+            .map(|attachment| attachment.size)
+            .sum()
+    }
+}
@@ -0,0 +1,12 @@
+//! # Book Metadata Models
+//!
+//! This module defines the metadata read from e-book files.
+
+/// Metadata of an e-book
+#[derive(Debug, Default)]
+pub struct BookMetadata {
+    /// Title of the book
+    pub title: Option<String>,
+    /// Author of the book
+    pub author: Option<String>,
+}
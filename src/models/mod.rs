@@ -4,12 +4,36 @@
 
 mod azure;
 mod config;
+mod delivery;
 mod error;
+mod format;
+mod gmail;
+mod history;
 mod kindle;
+mod mail;
+mod metadata;
+mod oauth;
+mod quota;
 
-pub use azure::TokenResponse;
-pub use config::Config;
+pub use azure::{DeviceCodeResponse, TokenErrorResponse, TokenResponse};
+pub use config::{
+    AuthFlow, AzureConfig, ClientCertificateConfig, Config, DeliveryMode, EmlConfig, GoogleConfig,
+    HttpConfig, LoginConfig, MessageConfig, QuotaConfig, RetryConfig, SendmailConfig,
+    SentItemsCleanup, SmtpAuthMechanism, SmtpConfig, SmtpSecurity, TransportKind,
+};
+pub use delivery::DeliveryLog;
 pub use error::KindleError;
+pub use format::DocumentFormat;
+pub use history::{SendHistory, SendStatus};
+pub use mail::{IncomingMail, MailAttachment, OutgoingMail};
+pub use metadata::BookMetadata;
+pub use oauth::{AuthorizationSecrets, ClientAssertionClaims, ClientAssertionHeader};
+pub use quota::{QuotaSend, QuotaState};
 
 // These types are available for other modules but not currently used publicly
-pub(crate) use kindle::{Attachment, Body, Email, EmailAddress, Message, Recipient};
+pub(crate) use gmail::GmailMessage;
+pub(crate) use kindle::{
+    Attachment, AttachmentItem, Body, DraftMessage, Email, EmailAddress, InboxMessageList,
+    InternetMessageHeader, MailFolder, MailFolderList, MailFolderRequest, Message, MessageLocation,
+    MoveRequest, Recipient, UploadSession, UploadSessionRequest,
+};
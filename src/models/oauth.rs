@@ -0,0 +1,46 @@
+//! # OAuth Models
+//!
+//! This module defines the data structures shared by the OAuth sign-in flows.
+
+use serde::Serialize;
+
+/// Secrets binding an authorization request to the callback completing it
+#[derive(Debug)]
+pub struct AuthorizationSecrets {
+    /// Random value sent with the request and checked on the callback
+    pub state: String,
+    /// PKCE verifier sent with the token request
+    pub code_verifier: String,
+    /// PKCE challenge (S256) sent with the authorization request
+    pub code_challenge: String,
+}
+
+/// JOSE header of a client assertion signed with a certificate
+#[derive(Debug, Serialize)]
+pub struct ClientAssertionHeader {
+    /// Signature algorithm
+    pub alg: &'static str,
+    /// Type of the token
+    pub typ: &'static str,
+    /// Base64url-encoded SHA-1 thumbprint of the certificate
+    pub x5t: String,
+}
+
+/// Claims of a client assertion, as expected by the Microsoft identity platform
+#[derive(Debug, Serialize)]
+pub struct ClientAssertionClaims {
+    /// Token endpoint the assertion is sent to
+    pub aud: String,
+    /// Client ID of the application
+    pub iss: String,
+    /// Client ID of the application
+    pub sub: String,
+    /// Random identifier of the assertion
+    pub jti: String,
+    /// Time before which the assertion is invalid, in seconds since the epoch
+    pub nbf: i64,
+    /// Time the assertion was issued, in seconds since the epoch
+    pub iat: i64,
+    /// Time the assertion expires, in seconds since the epoch
+    pub exp: i64,
+}
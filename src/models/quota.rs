@@ -0,0 +1,62 @@
+//! # Quota Models
+//!
+//! This module defines the record of the emails sent recently, used to stay within
+//! the sending limits of the mail providers across runs.
+
+use serde::{Deserialize, Serialize};
+
+/// Length of the window of the daily limits, in seconds
+const DAY_SECS: i64 = 24 * 60 * 60;
+
+/// Usage of the sending limits, persisted between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct QuotaState {
+    /// Emails sent in the last 24 hours
+    #[serde(default)]
+    pub sends: Vec<QuotaSend>,
+    /// Number of emails that can be sent right away, negative when sends are waiting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens: Option<f64>,
+    /// Timestamp in milliseconds of the last update of the tokens
+    #[serde(default)]
+    pub refilled_at_ms: i64,
+}
+
+/// Email counted in the daily limits
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuotaSend {
+    /// Timestamp of the send
+    pub sent_at: i64,
+    /// Number of recipients of the email
+    pub recipients: usize,
+}
+
+impl QuotaState {
+    /// Forget the emails sent outside of the window of the daily limits
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Current timestamp
+    pub fn prune(&mut self, now: i64) {
+        self.sends.retain(|send| send.sent_at > now - DAY_SECS);
+    }
+
+    /// Get the usage of the daily limits
+    ///
+    /// # Returns
+    ///
+    /// * `(usize, usize)` - The number of emails and recipients in the window
+    pub fn daily_usage(&self) -> (usize, usize) {
+        let recipients = self.sends.iter().map(|send| send.recipients).sum();
+        (self.sends.len(), recipients)
+    }
+
+    /// Get the time at which the oldest email leaves the window of the daily limits
+    ///
+    /// # Returns
+    ///
+    /// * `Option<i64>` - Timestamp at which room is made, or None if no email was sent
+    pub fn next_release(&self) -> Option<i64> {
+        self.sends.iter().map(|send| send.sent_at + DAY_SECS).min()
+    }
+}
@@ -0,0 +1,132 @@
+//! # Client Assertion Service
+//!
+//! This module signs the JWT client assertions authenticating an application with
+//! a certificate instead of a client secret, in the client credentials flow.
+
+use aws_lc_rs::digest::{self, SHA1_FOR_LEGACY_USE_ONLY};
+use aws_lc_rs::rand::SystemRandom;
+use aws_lc_rs::signature::{RSA_PKCS1_SHA256, RsaKeyPair};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use rand::Rng;
+use rustls_pki_types::pem::PemObject;
+use rustls_pki_types::{CertificateDer, PrivateKeyDer};
+use serde::Serialize;
+
+use crate::models::{
+    ClientAssertionClaims, ClientAssertionHeader, ClientCertificateConfig, KindleError,
+};
+
+/// Lifetime of a client assertion, in seconds
+const ASSERTION_LIFETIME_SECS: i64 = 600;
+
+/// Service for signing client assertions
+pub struct AssertionService {}
+
+impl AssertionService {
+    /// Sign a client assertion with the certificate of the application
+    ///
+    /// # Arguments
+    ///
+    /// * `certificate` - The certificate and its private key
+    /// * `client_id` - Client ID of the application
+    /// * `audience` - Token endpoint the assertion is sent to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - The signed JWT or an error
+    pub fn sign(
+        certificate: &ClientCertificateConfig,
+        client_id: &str,
+        audience: &str,
+    ) -> Result<String, KindleError> {
+        let certificate_der = CertificateDer::from_pem_file(&certificate.certificate_path)
+            .map_err(|e| KindleError {
+                message: format!(
+                    "Failed to read certificate {}: {}",
+                    certificate.certificate_path, e
+                ),
+            })?;
+        let key_pair = Self::read_key_pair(&certificate.private_key_path)?;
+
+        let now = Utc::now().timestamp();
+        let header = ClientAssertionHeader {
+            alg: "RS256",
+            typ: "JWT",
+            x5t: general_purpose::URL_SAFE_NO_PAD
+                .encode(digest::digest(&SHA1_FOR_LEGACY_USE_ONLY, &certificate_der)),
+        };
+        let claims = ClientAssertionClaims {
+            aud: audience.to_string(),
+            iss: client_id.to_string(),
+            sub: client_id.to_string(),
+            jti: format!("{:032x}", rand::rng().random::<u128>()),
+            nbf: now,
+            iat: now,
+            exp: now + ASSERTION_LIFETIME_SECS,
+        };
+
+        let signing_input = format!("{}.{}", Self::encode(&header)?, Self::encode(&claims)?);
+        let mut signature = vec![0u8; key_pair.public_modulus_len()];
+        key_pair
+            .sign(
+                &RSA_PKCS1_SHA256,
+                &SystemRandom::new(),
+                signing_input.as_bytes(),
+                &mut signature,
+            )
+            .map_err(|e| KindleError {
+                message: format!("Failed to sign client assertion: {}", e),
+            })?;
+
+        Ok(format!(
+            "{}.{}",
+            signing_input,
+            general_purpose::URL_SAFE_NO_PAD.encode(signature)
+        ))
+    }
+
+    /// Read the RSA private key of a certificate
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - PEM file of the key, in PKCS#8 or PKCS#1 format
+    ///
+    /// # Returns
+    ///
+    /// * `Result<RsaKeyPair, KindleError>` - The key pair or an error
+    fn read_key_pair(path: &str) -> Result<RsaKeyPair, KindleError> {
+        let key = PrivateKeyDer::from_pem_file(path).map_err(|e| KindleError {
+            message: format!("Failed to read private key {}: {}", path, e),
+        })?;
+
+        let key_pair = match &key {
+            PrivateKeyDer::Pkcs8(der) => RsaKeyPair::from_pkcs8(der.secret_pkcs8_der()),
+            PrivateKeyDer::Pkcs1(der) => RsaKeyPair::from_der(der.secret_pkcs1_der()),
+            _ => {
+                return Err(KindleError {
+                    message: format!("Private key {} is not an RSA key", path),
+                });
+            }
+        };
+        key_pair.map_err(|e| KindleError {
+            message: format!("Invalid private key {}: {}", path, e),
+        })
+    }
+
+    /// Encode a part of a JWT
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - The header or claims
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - The JSON in unpadded base64url or an error
+    fn encode<T: Serialize>(value: &T) -> Result<String, KindleError> {
+        let json = serde_json::to_vec(value).map_err(|e| KindleError {
+            message: format!("Failed to serialize client assertion: {}", e),
+        })?;
+        Ok(general_purpose::URL_SAFE_NO_PAD.encode(json))
+    }
+}
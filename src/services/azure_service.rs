@@ -3,32 +3,42 @@
 //! This module provides services for authenticating with the Microsoft Azure API,
 //! including token acquisition, refresh, and storage.
 
-use std::collections::HashMap;
 use std::error::Error;
-use std::fs;
-use std::fs::File;
-use std::io::{Read, Write};
-use std::path::PathBuf;
-use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use chrono::Utc;
 use log::info;
-use reqwest::Client;
-use tokio::sync::oneshot;
-use warp::Filter;
+use reqwest::{Client, Url};
 
-use crate::models::{KindleError, TokenResponse};
+use crate::models::{
+    AuthFlow, AzureConfig, DeviceCodeResponse, KindleError, LoginConfig, TokenErrorResponse,
+    TokenResponse,
+};
+use crate::services::{
+    AssertionService, CallbackService, PkceService, RetryService, StateService, TokenService,
+};
+
+/// Type of the client assertions signed with a certificate
+const JWT_BEARER_ASSERTION_TYPE: &str = "urn:ietf:params:oauth:client-assertion-type:jwt-bearer";
+
+/// Grant type of the token requests of the device code flow
+const DEVICE_CODE_GRANT_TYPE: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// Increase of the polling interval requested by a `slow_down` error, per RFC 8628
+const SLOW_DOWN_INCREMENT: Duration = Duration::from_secs(5);
 
 /// Service for handling Azure authentication and API operations
 pub struct AzureService<'a> {
-    /// Azure application client ID
-    pub client_id: &'a str,
-    /// Azure application client secret
-    pub client_secret: &'a str,
-    /// Azure tenant ID
-    pub tenant_id: &'a str,
+    /// Azure API configuration (client, tenant and endpoints)
+    pub config: &'a AzureConfig,
     /// OAuth callback URL for redirection after authentication
     pub callback_url: &'a str,
+    /// Behavior of the browser sign-in
+    pub login: &'a LoginConfig,
+    /// Shared HTTP client used for the token requests
+    pub client: &'a Client,
+    /// Retry policy applied to the token requests
+    pub retry_service: RetryService<'a>,
 }
 
 impl<'a> AzureService<'a> {
@@ -36,25 +46,28 @@ impl<'a> AzureService<'a> {
     ///
     /// # Arguments
     ///
-    /// * `client_id` - The Azure application client ID
-    /// * `client_secret` - The Azure application client secret
-    /// * `tenant_id` - The Azure tenant ID
+    /// * `config` - The Azure API configuration
     /// * `callback_url` - The OAuth callback URL
+    /// * `login` - Behavior of the browser sign-in
+    /// * `client` - The shared HTTP client
+    /// * `retry_service` - The retry policy applied to the token requests
     ///
     /// # Returns
     ///
     /// * `Self` - A new AzureService instance
     pub fn new(
-        client_id: &'a str,
-        client_secret: &'a str,
-        tenant_id: &'a str,
+        config: &'a AzureConfig,
         callback_url: &'a str,
+        login: &'a LoginConfig,
+        client: &'a Client,
+        retry_service: RetryService<'a>,
     ) -> Self {
         AzureService {
-            client_id,
-            client_secret,
-            tenant_id,
+            config,
             callback_url,
+            login,
+            client,
+            retry_service,
         }
     }
 
@@ -63,7 +76,10 @@ impl<'a> AzureService<'a> {
     /// This method will:
     /// 1. Try to use a cached token if it's still valid
     /// 2. Try to refresh the token if it's expired but we have a refresh token
-    /// 3. Start a new authentication flow if needed
+    /// 3. Start a new authentication flow if needed, with the configured OAuth flow
+    ///
+    /// With the client credentials flow, a new app-only token is requested on every
+    /// run and never cached, as the application can get one at any time.
     ///
     /// # Returns
     ///
@@ -71,11 +87,24 @@ impl<'a> AzureService<'a> {
     pub async fn authenticate(&self) -> Result<String, KindleError> {
         info!("Authenticating with Azure...");
 
-        let auth_file_path = dirs::home_dir().unwrap().join(".kindle_sender/auth.json");
+        if self.config.auth_flow == AuthFlow::ClientCredentials {
+            return self
+                .sign_in_with_client_credentials()
+                .await
+                .map(|token_response| token_response.access_token);
+        }
+
+        let auth_file_path = StateService::path("auth.json");
+        let permissions = self.config.graph_permissions();
 
         // Check if the auth file exists and read the token
-        if let Ok(token_response) = Self::read_token_from_file(&auth_file_path) {
-            if token_response.is_token_valid() {
+        if let Ok(token_response) = TokenService::read_token_from_file(&auth_file_path) {
+            if !token_response.has_permissions(&permissions) {
+                info!(
+                    "The cached token was not granted {}, signing in again",
+                    permissions.join(", ")
+                );
+            } else if token_response.is_token_valid() {
                 return Ok(token_response.access_token);
             } else if let Some(refresh_token) = &token_response.refresh_token {
                 let new_token_response =
@@ -84,68 +113,258 @@ impl<'a> AzureService<'a> {
                         .map_err(|e| KindleError {
                             message: format!("Error refreshing token: {}", e),
                         })?;
-                Self::write_token_to_file(&auth_file_path, &new_token_response).map_err(|e| {
-                    KindleError {
+                TokenService::write_token_to_file(&auth_file_path, &new_token_response).map_err(
+                    |e| KindleError {
                         message: format!("Error writing token to file: {}", e),
-                    }
-                })?;
-                return Ok(new_token_response.access_token);
+                    },
+                )?;
+
+                if new_token_response.has_permissions(&permissions) {
+                    return Ok(new_token_response.access_token);
+                }
+                info!(
+                    "The refreshed token was not granted {}, signing in again",
+                    permissions.join(", ")
+                );
             }
         }
 
-        let scopes = "offline_access%20Mail.Send";
+        let mut token_response = match self.config.auth_flow {
+            AuthFlow::AuthorizationCode => self.sign_in_with_browser().await?,
+            AuthFlow::DeviceCode => self.sign_in_with_device_code().await?,
+            AuthFlow::ClientCredentials => unreachable!("app-only tokens are not cached"),
+        };
 
-        let auth_url = format!(
-            "https://login.microsoftonline.com/{}/oauth2/v2.0/authorize?client_id={}&response_type=code&redirect_uri={}&response_mode=query&scope={}",
-            self.tenant_id, self.client_id, self.callback_url, scopes
-        );
+        // Calculate the expiration time
+        token_response.expires_at = Some(Utc::now().timestamp() + token_response.expires_in as i64);
 
-        info!(
-            "Please open the following URL in your browser:\n{}",
-            auth_url
-        );
+        // Store the token response
+        TokenService::write_token_to_file(&auth_file_path, &token_response).map_err(|e| {
+            KindleError {
+                message: format!("Error writing token to file: {}", e),
+            }
+        })?;
 
-        // Channel to receive the auth code
-        let (tx, rx) = oneshot::channel();
-        let tx = Arc::new(Mutex::new(Some(tx)));
-
-        // Warp filter to handle the redirect
-        let callback_route = warp::path("callback")
-            .and(warp::query::<HashMap<String, String>>())
-            .map(move |query: HashMap<String, String>| {
-                if let Some(code) = query.get("code") {
-                    if let Some(tx) = tx.lock().unwrap().take() {
-                        tx.send(code.clone()).ok();
-                    }
-                }
-                warp::reply::html("You can close this tab and return to the CLI.")
-            });
+        Ok(token_response.access_token)
+    }
+
+    /// Sign in with the authorization code flow
+    ///
+    /// The user opens the authorization URL in a browser, which is then redirected
+    /// to the local callback server with the authorization code. The request is bound
+    /// to the callback by a random state and a PKCE challenge.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TokenResponse, KindleError>` - The token response or an error
+    async fn sign_in_with_browser(&self) -> Result<TokenResponse, KindleError> {
+        let scopes = format!("offline_access {}", self.graph_scopes());
+        let secrets = PkceService::generate();
 
-        // Start the warp server
-        tokio::spawn(warp::serve(callback_route).run(([127, 0, 0, 1], 8080)));
+        // Listen for the callback before the user is sent to the sign-in page
+        let callback = CallbackService::bind(self.callback_url).await?;
+        let redirect_uri = callback.redirect_uri.clone();
 
-        // Wait for the auth code
-        let auth_code = rx.await.map_err(|_| KindleError {
-            message: "Failed to receive auth code".to_string(),
+        let auth_url = Url::parse_with_params(
+            &format!("{}/oauth2/v2.0/authorize", self.config.authority_endpoint()),
+            &[
+                ("client_id", self.config.client_id.as_str()),
+                ("response_type", "code"),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("response_mode", "query"),
+                ("scope", scopes.as_str()),
+                ("state", secrets.state.as_str()),
+                ("code_challenge", secrets.code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| KindleError {
+            message: format!("Error building authorization URL: {}", e),
         })?;
 
+        // Wait for the auth code on the callback server
+        let auth_code = callback
+            .wait_for_code(&auth_url, &secrets.state, self.login)
+            .await?;
+
         // Exchange the auth code for a token
-        let mut token_response = self
-            .exchange_code_for_token(auth_code, self.callback_url)
+        self.exchange_code_for_token(auth_code, &redirect_uri, &secrets.code_verifier)
             .await
             .map_err(|e| KindleError {
                 message: format!("Error exchanging code for token: {}", e),
+            })
+    }
+
+    /// Sign in with the device code flow
+    ///
+    /// The user enters the displayed code on the verification page, from any device,
+    /// while the token endpoint is polled until the sign-in completes.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TokenResponse, KindleError>` - The token response or an error
+    async fn sign_in_with_device_code(&self) -> Result<TokenResponse, KindleError> {
+        let device_code = self.request_device_code().await?;
+
+        info!(
+            "To sign in, open {} on any device and enter the code {}",
+            device_code.verification_uri, device_code.user_code
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(device_code.expires_in);
+        let mut interval = Duration::from_secs(device_code.interval.max(1));
+        let params = self.with_client_secret(vec![
+            ("client_id", self.config.client_id.as_str()),
+            ("grant_type", DEVICE_CODE_GRANT_TYPE),
+            ("device_code", device_code.device_code.as_str()),
+        ]);
+
+        loop {
+            tokio::time::sleep(interval).await;
+            if Instant::now() >= deadline {
+                return Err(KindleError {
+                    message: "The device code expired before the sign-in completed".to_string(),
+                });
+            }
+
+            let response = self
+                .retry_service
+                .send("Device code token request", || {
+                    self.client.post(self.token_endpoint()).form(&params)
+                })
+                .await
+                .map_err(|e| KindleError {
+                    message: format!("Error requesting token: {}", e),
+                })?;
+
+            if response.status().is_success() {
+                return response.json().await.map_err(|e| KindleError {
+                    message: format!("Error parsing token response: {}", e),
+                });
+            }
+
+            let status = response.status();
+            let error: TokenErrorResponse = response.json().await.map_err(|e| KindleError {
+                message: format!("Error parsing token error response ({}): {}", status, e),
+            })?;
+            match error.error.as_str() {
+                "authorization_pending" => {}
+                "slow_down" => interval += SLOW_DOWN_INCREMENT,
+                _ => {
+                    return Err(KindleError {
+                        message: format!(
+                            "Device code sign-in failed: {}",
+                            error.error_description.unwrap_or(error.error)
+                        ),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Request a device code and a user code for the device code flow
+    ///
+    /// # Returns
+    ///
+    /// * `Result<DeviceCodeResponse, KindleError>` - The codes or an error
+    async fn request_device_code(&self) -> Result<DeviceCodeResponse, KindleError> {
+        let scope = format!("offline_access {}", self.graph_scopes());
+        let params = [
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", scope.as_str()),
+        ];
+
+        let response = self
+            .retry_service
+            .send("Device code request", || {
+                self.client
+                    .post(format!(
+                        "{}/oauth2/v2.0/devicecode",
+                        self.config.authority_endpoint()
+                    ))
+                    .form(&params)
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Error requesting device code: {}", e),
             })?;
 
-        // Calculate the expiration time
-        token_response.expires_at = Some(Utc::now().timestamp() + token_response.expires_in as i64);
+        if !response.status().is_success() {
+            return Err(KindleError::from_response("Error requesting device code", response).await);
+        }
 
-        // Store the token response
-        Self::write_token_to_file(&auth_file_path, &token_response).map_err(|e| KindleError {
-            message: format!("Error writing token to file: {}", e),
-        })?;
+        response.json().await.map_err(|e| KindleError {
+            message: format!("Error parsing device code response: {}", e),
+        })
+    }
 
-        Ok(token_response.access_token)
+    /// Get an app-only token with the client credentials flow
+    ///
+    /// The application authenticates with a JWT assertion signed by its certificate,
+    /// or with its client secret, and is granted the application permissions consented
+    /// in the tenant. As there is no signed-in user, the emails are sent from the
+    /// `send_as` mailbox.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TokenResponse, KindleError>` - The token response or an error
+    async fn sign_in_with_client_credentials(&self) -> Result<TokenResponse, KindleError> {
+        if self.config.send_as.is_none() {
+            return Err(KindleError {
+                message: "The client credentials flow requires the sending mailbox in `send_as`"
+                    .to_string(),
+            });
+        }
+
+        let token_endpoint = self.token_endpoint();
+        let scope = self.config.graph_scope(".default");
+        let mut params = vec![
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", scope.as_str()),
+            ("grant_type", "client_credentials"),
+        ];
+
+        let assertion = match &self.config.client_certificate {
+            Some(certificate) => Some(AssertionService::sign(
+                certificate,
+                &self.config.client_id,
+                &token_endpoint,
+            )?),
+            None => None,
+        };
+        match (&assertion, self.config.client_secret()) {
+            (Some(assertion), _) => {
+                params.push(("client_assertion_type", JWT_BEARER_ASSERTION_TYPE));
+                params.push(("client_assertion", assertion));
+            }
+            (None, Some(secret)) => params.push(("client_secret", secret)),
+            (None, None) => {
+                return Err(KindleError {
+                    message: "The client credentials flow requires `client_certificate` or `client_secret`"
+                        .to_string(),
+                });
+            }
+        }
+
+        let response = self
+            .retry_service
+            .send("Client credentials token request", || {
+                self.client.post(&token_endpoint).form(&params)
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Error requesting token: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(
+                KindleError::from_response("Error requesting app-only token", response).await,
+            );
+        }
+
+        response.json().await.map_err(|e| KindleError {
+            message: format!("Error parsing token response: {}", e),
+        })
     }
 
     /// Exchange an authorization code for an access token
@@ -154,6 +373,7 @@ impl<'a> AzureService<'a> {
     ///
     /// * `auth_code` - Authorization code received from the OAuth redirect
     /// * `redirect_uri` - The redirect URI used in the initial authorization request
+    /// * `code_verifier` - The PKCE verifier of the authorization request
     ///
     /// # Returns
     ///
@@ -162,24 +382,23 @@ impl<'a> AzureService<'a> {
         &self,
         auth_code: String,
         redirect_uri: &str,
+        code_verifier: &str,
     ) -> Result<TokenResponse, Box<dyn Error>> {
-        let client = Client::new();
-        let params = [
-            ("client_id", self.client_id),
-            ("scope", "Mail.Send"),
+        let scope = self.graph_scopes();
+        let params = self.with_client_secret(vec![
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", scope.as_str()),
             ("code", auth_code.as_str()),
             ("redirect_uri", redirect_uri),
             ("grant_type", "authorization_code"),
-            ("client_secret", self.client_secret),
-        ];
+            ("code_verifier", code_verifier),
+        ]);
 
-        let res = client
-            .post(format!(
-                "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
-                self.tenant_id
-            ))
-            .form(&params)
-            .send()
+        let res = self
+            .retry_service
+            .send("Token request", || {
+                self.client.post(self.token_endpoint()).form(&params)
+            })
             .await?
             .json()
             .await?;
@@ -200,22 +419,19 @@ impl<'a> AzureService<'a> {
         &self,
         refresh_token: &str,
     ) -> Result<TokenResponse, Box<dyn Error>> {
-        let client = Client::new();
-        let params = [
-            ("client_id", self.client_id),
-            ("scope", "https://graph.microsoft.com/.default"),
+        let scope = self.config.graph_scope(".default");
+        let params = self.with_client_secret(vec![
+            ("client_id", self.config.client_id.as_str()),
+            ("scope", scope.as_str()),
             ("refresh_token", refresh_token),
             ("grant_type", "refresh_token"),
-            ("client_secret", self.client_secret),
-        ];
+        ]);
 
-        let res = client
-            .post(format!(
-                "https://login.microsoftonline.com/{}/oauth2/v2.0/token",
-                self.tenant_id
-            ))
-            .form(&params)
-            .send()
+        let res = self
+            .retry_service
+            .send("Token refresh", || {
+                self.client.post(self.token_endpoint()).form(&params)
+            })
             .await?
             .json::<TokenResponse>()
             .await?;
@@ -223,47 +439,48 @@ impl<'a> AzureService<'a> {
         Ok(res)
     }
 
-    /// Read a token from a file
+    /// Add the client secret to the parameters of a token request
+    ///
+    /// Applications registered as public clients have no secret and must not send
+    /// one, the authorization code is then bound to the client by PKCE only.
     ///
     /// # Arguments
     ///
-    /// * `file_path` - Path to the file containing the token
+    /// * `params` - The other parameters of the request
     ///
     /// # Returns
     ///
-    /// * `Result<TokenResponse, Box<dyn Error>>` - Token response or an error
-    fn read_token_from_file(file_path: &PathBuf) -> Result<TokenResponse, Box<dyn Error>> {
-        if file_path.exists() {
-            let mut file = File::open(file_path)?;
-            let mut contents = String::new();
-            file.read_to_string(&mut contents)?;
-            let token_response: TokenResponse = serde_json::from_str(&contents)?;
-            Ok(token_response)
-        } else {
-            Err("File not found".into())
+    /// * `Vec<(&str, &str)>` - The parameters, with the secret if one is configured
+    fn with_client_secret<'p>(
+        &'p self,
+        mut params: Vec<(&'p str, &'p str)>,
+    ) -> Vec<(&'p str, &'p str)> {
+        if let Some(secret) = self.config.client_secret() {
+            params.push(("client_secret", secret));
         }
+        params
     }
 
-    /// Write a token to a file
+    /// Get the Microsoft Graph scopes requested when signing in
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `file_path` - Path to write the token to
-    /// * `token_response` - Token response to write
+    /// * `String` - The space-separated scopes
+    fn graph_scopes(&self) -> String {
+        self.config
+            .graph_permissions()
+            .iter()
+            .map(|permission| self.config.graph_scope(permission))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Get the OAuth token endpoint of the tenant
     ///
     /// # Returns
     ///
-    /// * `Result<(), Box<dyn Error>>` - Success or an error
-    fn write_token_to_file(
-        file_path: &PathBuf,
-        token_response: &TokenResponse,
-    ) -> Result<(), Box<dyn Error>> {
-        if let Some(parent_dir) = file_path.parent() {
-            fs::create_dir_all(parent_dir)?;
-        }
-        let mut file = File::create(file_path)?;
-        let json = serde_json::to_string(token_response)?;
-        file.write_all(json.as_bytes())?;
-        Ok(())
+    /// * `String` - The token endpoint URL
+    fn token_endpoint(&self) -> String {
+        format!("{}/oauth2/v2.0/token", self.config.authority_endpoint())
     }
 }
@@ -0,0 +1,79 @@
+//! # Browser Service
+//!
+//! This module opens URLs in the system web browser.
+
+use std::process::Stdio;
+
+use log::{debug, warn};
+use tokio::process::Command;
+
+/// Service for opening URLs in the system web browser
+pub struct BrowserService {}
+
+impl BrowserService {
+    /// Open a URL in the system web browser
+    ///
+    /// The launcher is not waited for, as it may stay in the foreground as long as the
+    /// browser runs. It is reaped in the background, and a failure is logged then.
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to open
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the launcher was started, false otherwise
+    pub fn open(url: &str) -> bool {
+        let child = Self::open_command(url)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                debug!("Failed to launch the browser: {}", e);
+                return false;
+            }
+        };
+
+        tokio::spawn(async move {
+            match child.wait().await {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    debug!("Browser launcher exited with {}", status);
+                    warn!("Failed to open the browser, open the URL manually");
+                }
+                Err(e) => debug!("Failed to wait for the browser launcher: {}", e),
+            }
+        });
+        true
+    }
+
+    /// Build the command opening a URL with the launcher of the platform
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The URL to open
+    ///
+    /// # Returns
+    ///
+    /// * `Command` - The command to run
+    fn open_command(url: &str) -> Command {
+        if cfg!(target_os = "windows") {
+            // Unlike `cmd /C start`, the URL is not parsed by the shell, which would split it at `&`
+            let mut command = Command::new("rundll32");
+            command.args(["url.dll,FileProtocolHandler", url]);
+            command
+        } else if cfg!(target_os = "macos") {
+            let mut command = Command::new("open");
+            command.arg(url);
+            command
+        } else {
+            let mut command = Command::new("xdg-open");
+            command.arg(url);
+            command
+        }
+    }
+}
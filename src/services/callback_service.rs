@@ -0,0 +1,277 @@
+//! # OAuth Callback Service
+//!
+//! This module provides a local HTTP server receiving the authorization code at the end
+//! of the browser-based OAuth authorization code flow.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use reqwest::Url;
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use warp::http::StatusCode;
+use warp::path::FullPath;
+use warp::{Filter, Reply};
+
+use crate::models::{KindleError, LoginConfig};
+use crate::services::BrowserService;
+
+/// Longest wait for the callback server to finish serving its last response
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Local server receiving the OAuth authorization code
+pub struct CallbackService {
+    /// Redirect URI to send with the authorization request, with the actual port
+    pub redirect_uri: String,
+    /// Path of the callback route
+    path: String,
+    /// Listener bound to the address of the redirect URI
+    listener: TcpListener,
+}
+
+impl CallbackService {
+    /// Bind the callback server to the address of a callback URI
+    ///
+    /// The server listens on the host and port of the URI, or on a free port picked
+    /// by the system when the port is 0, and serves its path.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback_uri` - The configured callback URI (e.g. "http://localhost:8080/callback")
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self, KindleError>` - The bound callback server or an error
+    pub async fn bind(callback_uri: &str) -> Result<Self, KindleError> {
+        let mut url = Url::parse(callback_uri).map_err(|e| KindleError {
+            message: format!("Invalid callback URI {}: {}", callback_uri, e),
+        })?;
+        if url.scheme() != "http" {
+            return Err(KindleError {
+                message: format!(
+                    "The callback URI {} must use http, the callback server does not serve https",
+                    callback_uri
+                ),
+            });
+        }
+
+        let address = Self::socket_address(&url)?;
+        let listener = TcpListener::bind(address).await.map_err(|e| KindleError {
+            message: format!(
+                "Cannot listen on {} for the sign-in callback: {}. Close the program using this \
+                 port, or change the port of callback_uri (0 picks a free port)",
+                address, e
+            ),
+        })?;
+
+        // The redirect URI must match the registered one, it is only rewritten with
+        // the port picked by the system
+        let mut redirect_uri = callback_uri.to_string();
+        if address.port() == 0 {
+            let port = listener
+                .local_addr()
+                .map_err(|e| KindleError {
+                    message: format!("Failed to get the address of the callback server: {}", e),
+                })?
+                .port();
+            url.set_port(Some(port)).map_err(|_| KindleError {
+                message: format!("Invalid callback URI {}", callback_uri),
+            })?;
+            redirect_uri = url.to_string();
+        }
+        debug!("Callback server listening for {}", redirect_uri);
+
+        Ok(CallbackService {
+            redirect_uri,
+            path: url.path().to_string(),
+            listener,
+        })
+    }
+
+    /// Send the user to the sign-in page and wait for the authorization code
+    ///
+    /// The sign-in page is opened in the system browser unless disabled, its URL is
+    /// printed in any case. Callbacks whose state does not match the authorization
+    /// request are rejected with an error page, and the server keeps waiting for the
+    /// expected one. The server is shut down once the sign-in completes, fails or
+    /// times out.
+    ///
+    /// # Arguments
+    ///
+    /// * `auth_url` - URL of the sign-in page
+    /// * `expected_state` - The state sent with the authorization request
+    /// * `login` - Behavior of the browser sign-in
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - The authorization code or an error
+    pub async fn wait_for_code(
+        self,
+        auth_url: &Url,
+        expected_state: &str,
+        login: &LoginConfig,
+    ) -> Result<String, KindleError> {
+        let expected_state = expected_state.to_string();
+        let expected_path = self.path;
+
+        // Channel to receive the auth code
+        let (tx, rx) = oneshot::channel();
+        let tx = Arc::new(Mutex::new(Some(tx)));
+
+        // Warp filter to handle the redirect
+        let callback_route = warp::path::full()
+            .and(warp::query::<HashMap<String, String>>())
+            .map(move |path: FullPath, query: HashMap<String, String>| {
+                if path.as_str() != expected_path {
+                    return warp::reply::with_status(
+                        warp::reply::html("Not found"),
+                        StatusCode::NOT_FOUND,
+                    )
+                    .into_response();
+                }
+
+                if query.get("state") != Some(&expected_state) {
+                    warn!("Rejected a sign-in callback that does not match the sign-in request");
+                    return warp::reply::with_status(
+                        warp::reply::html(
+                            "Sign-in rejected: this page does not belong to the sign-in \
+                             started by the CLI. Start the sign-in again from the CLI.",
+                        ),
+                        StatusCode::BAD_REQUEST,
+                    )
+                    .into_response();
+                }
+
+                // The authorization server reports a denied consent or a misconfiguration
+                if let Some(error) = query.get("error") {
+                    let description = query
+                        .get("error_description")
+                        .map(|description| format!("{}: {}", error, description))
+                        .unwrap_or_else(|| error.clone());
+                    let page = format!(
+                        "Sign-in failed: {}. You can close this tab and return to the CLI.",
+                        Self::escape_html(&description)
+                    );
+                    if let Some(tx) = tx.lock().unwrap().take() {
+                        tx.send(Err(KindleError {
+                            message: format!("Sign-in failed: {}", description),
+                        }))
+                        .ok();
+                    }
+                    return warp::reply::with_status(
+                        warp::reply::html(page),
+                        StatusCode::BAD_REQUEST,
+                    )
+                    .into_response();
+                }
+
+                match query.get("code") {
+                    Some(code) => {
+                        if let Some(tx) = tx.lock().unwrap().take() {
+                            tx.send(Ok(code.clone())).ok();
+                        }
+                        warp::reply::html("You can close this tab and return to the CLI.")
+                            .into_response()
+                    }
+                    None => warp::reply::with_status(
+                        warp::reply::html("Sign-in failed: no authorization code received."),
+                        StatusCode::BAD_REQUEST,
+                    )
+                    .into_response(),
+                }
+            });
+
+        // Start the warp server, stopped once the code is received
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(
+            warp::serve(callback_route)
+                .incoming(self.listener)
+                .graceful(async {
+                    shutdown_rx.await.ok();
+                })
+                .run(),
+        );
+
+        info!(
+            "Please open the following URL in your browser:\n{}",
+            auth_url
+        );
+        if login.open_browser && !BrowserService::open(auth_url.as_str()) {
+            warn!("Failed to open the browser, open the URL manually");
+        }
+
+        // Wait for the auth code
+        let timeout = Duration::from_secs(login.timeout_secs);
+        let result = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(KindleError {
+                message: "Failed to receive auth code".to_string(),
+            }),
+            Err(_) => Err(KindleError {
+                message: format!(
+                    "Sign-in did not complete within {} seconds",
+                    login.timeout_secs
+                ),
+            }),
+        };
+
+        // Let the server send its last response before it stops
+        shutdown_tx.send(()).ok();
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, server)
+            .await
+            .is_err()
+        {
+            debug!("Callback server did not stop in time");
+        }
+
+        result
+    }
+
+    /// Escape text inserted in an HTML page
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The escaped text
+    fn escape_html(text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    }
+
+    /// Get the socket address to listen on for a callback URI
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The callback URI
+    ///
+    /// # Returns
+    ///
+    /// * `Result<SocketAddr, KindleError>` - The socket address or an error
+    fn socket_address(url: &Url) -> Result<SocketAddr, KindleError> {
+        let port = url.port_or_known_default().unwrap_or_default();
+
+        // Browsers resolve localhost to the IPv4 loopback first
+        let host = url.host_str().unwrap_or("localhost");
+        if host.eq_ignore_ascii_case("localhost") {
+            return Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port));
+        }
+
+        // IPv6 addresses are written between brackets in URLs
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        (host, port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .ok_or_else(|| KindleError {
+                message: format!("Cannot resolve the host of the callback URI: {}", host),
+            })
+    }
+}
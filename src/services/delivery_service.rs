@@ -0,0 +1,36 @@
+//! # Delivery Tracking Service
+//!
+//! This module persists the per-receiver delivery records on disk between runs.
+
+use crate::models::{DeliveryLog, KindleError};
+use crate::services::StateService;
+
+/// Name of the delivery records file in the application directory
+const DELIVERY_FILE_NAME: &str = "deliveries.json";
+
+/// Service for reading and writing the delivery records
+pub struct DeliveryService {}
+
+impl DeliveryService {
+    /// Read the delivery records, starting from empty records if there are none yet
+    ///
+    /// # Returns
+    ///
+    /// * `Result<DeliveryLog, KindleError>` - The delivery records or an error
+    pub fn read_log() -> Result<DeliveryLog, KindleError> {
+        StateService::read(DELIVERY_FILE_NAME, "delivery records")
+    }
+
+    /// Write the delivery records
+    ///
+    /// # Arguments
+    ///
+    /// * `log` - The delivery records to write
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    pub fn write_log(log: &DeliveryLog) -> Result<(), KindleError> {
+        StateService::write(DELIVERY_FILE_NAME, "delivery records", log)
+    }
+}
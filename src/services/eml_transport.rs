@@ -0,0 +1,112 @@
+//! # EML Transport
+//!
+//! This module writes each outgoing email as a complete `.eml` file in a directory
+//! instead of sending it, to exercise the whole pipeline without delivering anything.
+
+use std::fs;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use log::info;
+
+use crate::models::{EmlConfig, KindleError, OutgoingMail};
+use crate::services::{MailTransport, MimeService};
+
+/// Sender address used when none is configured
+const DEFAULT_FROM: &str = "kindle-sender@localhost";
+
+/// Mail transport writing emails as `.eml` files
+pub struct EmlTransport<'a> {
+    /// EML output configuration
+    pub config: &'a EmlConfig,
+}
+
+impl<'a> EmlTransport<'a> {
+    /// Create a new instance of EmlTransport
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - EML output configuration
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new EmlTransport instance
+    pub fn new(config: &'a EmlConfig) -> Self {
+        EmlTransport { config }
+    }
+
+    /// Write a message to a new file in the output directory
+    ///
+    /// The file name is made of the current timestamp and the name of the first
+    /// attachment. A numeric suffix is added if the file already exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The email being written
+    /// * `content` - The rendered MIME message
+    ///
+    /// # Returns
+    ///
+    /// * `Result<PathBuf, KindleError>` - Path of the written file or an error
+    fn write_message(&self, mail: &OutgoingMail, content: &[u8]) -> Result<PathBuf, KindleError> {
+        let stem = mail
+            .attachments
+            .first()
+            .map(|attachment| {
+                Path::new(&attachment.name)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_else(|| attachment.name.clone())
+            })
+            .unwrap_or_else(|| "message".to_string());
+        let base_name = format!("{}-{}", Utc::now().format("%Y%m%dT%H%M%S%.3f"), stem);
+
+        let mut suffix = 0;
+        loop {
+            let file_name = if suffix == 0 {
+                format!("{}.eml", base_name)
+            } else {
+                format!("{}-{}.eml", base_name, suffix)
+            };
+            let path = Path::new(&self.config.directory).join(file_name);
+
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(content).map_err(|e| KindleError {
+                        message: format!("Failed to write {:?}: {}", path, e),
+                    })?;
+                    return Ok(path);
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => suffix += 1,
+                Err(e) => {
+                    return Err(KindleError {
+                        message: format!("Failed to create {:?}: {}", path, e),
+                    });
+                }
+            }
+        }
+    }
+}
+
+impl MailTransport for EmlTransport<'_> {
+    async fn prepare(&mut self) -> Result<(), KindleError> {
+        fs::create_dir_all(&self.config.directory).map_err(|e| KindleError {
+            message: format!(
+                "Failed to create EML output directory {}: {}",
+                self.config.directory, e
+            ),
+        })?;
+        Ok(())
+    }
+
+    async fn send(&self, mail: &OutgoingMail) -> Result<(), KindleError> {
+        let from = self.config.from.as_deref().unwrap_or(DEFAULT_FROM);
+        let content = MimeService::build_message(mail, from)?.formatted();
+
+        let path = self.write_message(mail, &content)?;
+        info!("Email written to {:?}", path);
+        Ok(())
+    }
+}
@@ -0,0 +1,204 @@
+//! # Format Detection Service
+//!
+//! This module detects the format of e-book files from their extension and their
+//! content, so that attachments are sent with the right MIME type.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use log::warn;
+
+use crate::models::{DocumentFormat, KindleError};
+
+/// Number of bytes read from the start of a file to detect its format
+const SNIFF_SIZE: usize = 64 * 1024;
+
+/// Service for detecting the format of files
+pub struct FormatService {}
+
+impl FormatService {
+    /// Identify the format of a file
+    ///
+    /// The format is taken from the content of the file when it can be recognized,
+    /// and from its extension otherwise. A warning is logged when the extension does
+    /// not match the content, or when the format is not supported by Send-to-Kindle.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<DocumentFormat>, KindleError>` - The format, None if it is unknown, or an error
+    pub fn identify_format(file_path: &str) -> Result<Option<DocumentFormat>, KindleError> {
+        let path = Path::new(file_path);
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        let by_extension = path
+            .extension()
+            .and_then(|extension| DocumentFormat::from_extension(&extension.to_string_lossy()));
+        let detected = Self::detect_format(file_path)?;
+
+        let format = match (by_extension, detected) {
+            (Some(expected), Some(detected)) if !expected.matches(detected) => {
+                warn!(
+                    "File {} is named as {} but its content is {}, sending it as {}",
+                    name,
+                    expected.name(),
+                    detected.name(),
+                    detected.name()
+                );
+                Some(detected)
+            }
+            (Some(expected), None)
+                if !matches!(expected, DocumentFormat::Txt | DocumentFormat::Html) =>
+            {
+                warn!(
+                    "File {} is named as {} but its content was not recognized",
+                    name,
+                    expected.name()
+                );
+                Some(expected)
+            }
+            (Some(expected), _) => Some(expected),
+            (None, Some(detected)) => {
+                warn!(
+                    "File {} has an unexpected extension but its content is {}",
+                    name,
+                    detected.name()
+                );
+                Some(detected)
+            }
+            (None, None) => {
+                warn!(
+                    "File {} is not in a format supported by Send-to-Kindle",
+                    name
+                );
+                None
+            }
+        };
+
+        Ok(format)
+    }
+
+    /// Detect the format of a file from its first bytes
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Option<DocumentFormat>, KindleError>` - The format, None if it is not recognized, or an error
+    pub fn detect_format(file_path: &str) -> Result<Option<DocumentFormat>, KindleError> {
+        let file = File::open(file_path).map_err(|e| KindleError {
+            message: format!("Failed to open file: {}", e),
+        })?;
+
+        let mut header = Vec::with_capacity(SNIFF_SIZE);
+        file.take(SNIFF_SIZE as u64)
+            .read_to_end(&mut header)
+            .map_err(|e| KindleError {
+                message: format!("Failed to read file: {}", e),
+            })?;
+
+        Ok(Self::sniff(&header))
+    }
+
+    /// Detect a format from the first bytes of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The first bytes of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Option<DocumentFormat>` - The format, or None if it is not recognized
+    fn sniff(header: &[u8]) -> Option<DocumentFormat> {
+        if header.starts_with(b"%PDF-") {
+            Some(DocumentFormat::Pdf)
+        } else if header.starts_with(b"PK\x03\x04") {
+            Self::sniff_zip(header)
+        } else if header.starts_with(b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1") {
+            Some(DocumentFormat::Doc)
+        } else if header.starts_with(b"{\\rtf") {
+            Some(DocumentFormat::Rtf)
+        } else if header.starts_with(b"\xFF\xD8\xFF") {
+            Some(DocumentFormat::Jpeg)
+        } else if header.starts_with(b"\x89PNG\r\n\x1A\n") {
+            Some(DocumentFormat::Png)
+        } else if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+            Some(DocumentFormat::Gif)
+        } else if header.starts_with(b"BM") && header.get(6..10) == Some(&[0; 4]) {
+            Some(DocumentFormat::Bmp)
+        } else if header.get(60..68) == Some(b"BOOKMOBI") {
+            Some(DocumentFormat::Mobi)
+        } else {
+            Self::sniff_text(header)
+        }
+    }
+
+    /// Tell EPUB books and Word documents apart, both being ZIP archives
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The first bytes of the archive
+    ///
+    /// # Returns
+    ///
+    /// * `Option<DocumentFormat>` - The format, or None if it is not recognized
+    fn sniff_zip(header: &[u8]) -> Option<DocumentFormat> {
+        // An EPUB archive starts with an uncompressed "mimetype" entry
+        if header.get(30..58) == Some(b"mimetypeapplication/epub+zip") {
+            return Some(DocumentFormat::Epub);
+        }
+
+        // The entry names of a Word document are under "word/"
+        if header.windows(5).any(|window| window == b"word/") {
+            return Some(DocumentFormat::Docx);
+        }
+
+        None
+    }
+
+    /// Recognize HTML and plain text files
+    ///
+    /// # Arguments
+    ///
+    /// * `header` - The first bytes of the file
+    ///
+    /// # Returns
+    ///
+    /// * `Option<DocumentFormat>` - The format, or None if the file is not text
+    fn sniff_text(header: &[u8]) -> Option<DocumentFormat> {
+        if header.is_empty() || header.contains(&0) {
+            return None;
+        }
+
+        // The header may end in the middle of a multi-byte character
+        let text = match std::str::from_utf8(header) {
+            Ok(text) => text,
+            Err(e) if e.error_len().is_none() => {
+                std::str::from_utf8(&header[..e.valid_up_to()]).ok()?
+            }
+            Err(_) => return None,
+        };
+
+        let start = text
+            .trim_start_matches('\u{FEFF}')
+            .trim_start()
+            .chars()
+            .take(14)
+            .collect::<String>()
+            .to_ascii_lowercase();
+        if start.starts_with("<!doctype html") || start.starts_with("<html") {
+            Some(DocumentFormat::Html)
+        } else {
+            Some(DocumentFormat::Txt)
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! # Gmail Transport
+//!
+//! This module delivers emails through the Gmail API, sending raw RFC 822 messages with
+//! `users.messages.send` and switching to a resumable upload for large messages.
+
+use base64::{Engine as _, engine::general_purpose};
+use log::info;
+use reqwest::Client;
+
+use crate::models::{GmailMessage, GoogleConfig, KindleError, OutgoingMail};
+use crate::services::{GoogleService, MailTransport, MimeService};
+
+/// Endpoint sending messages given as base64 in a JSON payload
+const GMAIL_SEND_URL: &str = "https://gmail.googleapis.com/gmail/v1/users/me/messages/send";
+
+/// Endpoint sending messages through a resumable media upload
+const GMAIL_UPLOAD_URL: &str =
+    "https://gmail.googleapis.com/upload/gmail/v1/users/me/messages/send?uploadType=resumable";
+
+/// Largest raw message sent in a JSON payload (5 MB), larger ones use a resumable upload
+const SIMPLE_SEND_LIMIT: usize = 5 * 1024 * 1024;
+
+/// Mail transport sending emails with the Gmail API
+pub struct GmailTransport<'a> {
+    /// Google service used to obtain the access token
+    pub google_service: GoogleService<'a>,
+    /// Google API configuration
+    pub config: &'a GoogleConfig,
+    /// Shared HTTP client used for the Gmail API requests
+    pub client: &'a Client,
+    /// Access token obtained while preparing the transport
+    access_token: Option<String>,
+}
+
+impl<'a> GmailTransport<'a> {
+    /// Create a new instance of GmailTransport
+    ///
+    /// # Arguments
+    ///
+    /// * `google_service` - The Google service for authentication
+    /// * `config` - Google API configuration
+    /// * `client` - The shared HTTP client
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new GmailTransport instance
+    pub fn new(
+        google_service: GoogleService<'a>,
+        config: &'a GoogleConfig,
+        client: &'a Client,
+    ) -> Self {
+        GmailTransport {
+            google_service,
+            config,
+            client,
+            access_token: None,
+        }
+    }
+
+    /// Get the access token obtained while preparing the transport
+    ///
+    /// # Returns
+    ///
+    /// * `Result<&str, KindleError>` - The access token or an error
+    fn access_token(&self) -> Result<&str, KindleError> {
+        self.access_token.as_deref().ok_or_else(|| KindleError {
+            message: "Gmail transport used before authentication".to_string(),
+        })
+    }
+
+    /// Send a raw message as base64 in a JSON payload
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The RFC 822 message
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn send_simple(&self, raw: &[u8]) -> Result<(), KindleError> {
+        let message = GmailMessage {
+            raw: general_purpose::URL_SAFE.encode(raw),
+        };
+
+        let response = self
+            .client
+            .post(GMAIL_SEND_URL)
+            .bearer_auth(self.access_token()?)
+            .json(&message)
+            .send()
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to send email: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response("Failed to send email", response).await);
+        }
+
+        Ok(())
+    }
+
+    /// Send a raw message through a resumable upload
+    ///
+    /// # Arguments
+    ///
+    /// * `raw` - The RFC 822 message
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn send_resumable(&self, raw: Vec<u8>) -> Result<(), KindleError> {
+        info!(
+            "Message is larger than {} bytes, using a resumable upload",
+            SIMPLE_SEND_LIMIT
+        );
+
+        // Start the upload session
+        let response = self
+            .client
+            .post(GMAIL_UPLOAD_URL)
+            .bearer_auth(self.access_token()?)
+            .header("X-Upload-Content-Type", "message/rfc822")
+            .header("X-Upload-Content-Length", raw.len())
+            .json(&serde_json::json!({}))
+            .send()
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to start resumable upload: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(
+                KindleError::from_response("Failed to start resumable upload", response).await,
+            );
+        }
+
+        let upload_url = response
+            .headers()
+            .get("Location")
+            .and_then(|location| location.to_str().ok())
+            .ok_or_else(|| KindleError {
+                message: "Resumable upload response has no Location header".to_string(),
+            })?
+            .to_string();
+
+        // Upload the message
+        let response = self
+            .client
+            .put(upload_url)
+            .bearer_auth(self.access_token()?)
+            .header("Content-Type", "message/rfc822")
+            .body(raw)
+            .send()
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to upload message: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response("Failed to upload message", response).await);
+        }
+
+        Ok(())
+    }
+}
+
+impl MailTransport for GmailTransport<'_> {
+    async fn prepare(&mut self) -> Result<(), KindleError> {
+        self.access_token = Some(self.google_service.authenticate().await?);
+        Ok(())
+    }
+
+    async fn send(&self, mail: &OutgoingMail) -> Result<(), KindleError> {
+        let raw = MimeService::build_message(mail, &self.config.from)?.formatted();
+
+        if raw.len() <= SIMPLE_SEND_LIMIT {
+            self.send_simple(&raw).await?;
+        } else {
+            self.send_resumable(raw).await?;
+        }
+
+        info!("Email with attachment sent successfully!");
+        Ok(())
+    }
+}
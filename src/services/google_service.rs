@@ -0,0 +1,233 @@
+//! # Google Authentication Service
+//!
+//! This module provides services for authenticating with the Google OAuth API,
+//! including token acquisition, refresh, and storage.
+
+use std::error::Error;
+
+use chrono::Utc;
+use log::info;
+use reqwest::{Client, Url};
+
+use crate::models::{KindleError, LoginConfig, TokenResponse};
+use crate::services::{CallbackService, PkceService, StateService, TokenService};
+
+/// Google OAuth authorization endpoint
+const GOOGLE_AUTH_URL: &str = "https://accounts.google.com/o/oauth2/v2/auth";
+
+/// Google OAuth token endpoint
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Scope allowing the application to send emails on behalf of the user
+const GMAIL_SEND_SCOPE: &str = "https://www.googleapis.com/auth/gmail.send";
+
+/// Service for handling Google authentication
+pub struct GoogleService<'a> {
+    /// Google OAuth client ID
+    pub client_id: &'a str,
+    /// Google OAuth client secret
+    pub client_secret: &'a str,
+    /// OAuth callback URL for redirection after authentication
+    pub callback_url: &'a str,
+    /// Behavior of the browser sign-in
+    pub login: &'a LoginConfig,
+    /// Shared HTTP client used for the token requests
+    pub client: &'a Client,
+}
+
+impl<'a> GoogleService<'a> {
+    /// Create a new instance of GoogleService
+    ///
+    /// # Arguments
+    ///
+    /// * `client_id` - The Google OAuth client ID
+    /// * `client_secret` - The Google OAuth client secret
+    /// * `callback_url` - The OAuth callback URL
+    /// * `login` - Behavior of the browser sign-in
+    /// * `client` - The shared HTTP client
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new GoogleService instance
+    pub fn new(
+        client_id: &'a str,
+        client_secret: &'a str,
+        callback_url: &'a str,
+        login: &'a LoginConfig,
+        client: &'a Client,
+    ) -> Self {
+        GoogleService {
+            client_id,
+            client_secret,
+            callback_url,
+            login,
+            client,
+        }
+    }
+
+    /// Authenticate with Google and get an access token
+    ///
+    /// This method will:
+    /// 1. Try to use a cached token if it's still valid
+    /// 2. Try to refresh the token if it's expired but we have a refresh token
+    /// 3. Start a new authentication flow if needed
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - The access token or an error
+    pub async fn authenticate(&self) -> Result<String, KindleError> {
+        info!("Authenticating with Google...");
+
+        let auth_file_path = StateService::path("google_auth.json");
+
+        // Check if the auth file exists and read the token
+        if let Ok(token_response) = TokenService::read_token_from_file(&auth_file_path) {
+            if token_response.is_token_valid() {
+                return Ok(token_response.access_token);
+            } else if let Some(refresh_token) = &token_response.refresh_token {
+                let mut new_token_response = self
+                    .refresh_access_token(refresh_token)
+                    .await
+                    .map_err(|e| KindleError {
+                        message: format!("Error refreshing token: {}", e),
+                    })?;
+
+                // Google only returns the refresh token on the first authorization
+                if new_token_response.refresh_token.is_none() {
+                    new_token_response.refresh_token = Some(refresh_token.clone());
+                }
+
+                TokenService::write_token_to_file(&auth_file_path, &new_token_response).map_err(
+                    |e| KindleError {
+                        message: format!("Error writing token to file: {}", e),
+                    },
+                )?;
+                return Ok(new_token_response.access_token);
+            }
+        }
+
+        let secrets = PkceService::generate();
+
+        // Listen for the callback before the user is sent to the sign-in page
+        let callback = CallbackService::bind(self.callback_url).await?;
+        let redirect_uri = callback.redirect_uri.clone();
+
+        let auth_url = Url::parse_with_params(
+            GOOGLE_AUTH_URL,
+            &[
+                ("client_id", self.client_id),
+                ("redirect_uri", redirect_uri.as_str()),
+                ("response_type", "code"),
+                ("scope", GMAIL_SEND_SCOPE),
+                ("access_type", "offline"),
+                ("prompt", "consent"),
+                ("state", secrets.state.as_str()),
+                ("code_challenge", secrets.code_challenge.as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| KindleError {
+            message: format!("Error building authorization URL: {}", e),
+        })?;
+
+        // Wait for the auth code on the callback server
+        let auth_code = callback
+            .wait_for_code(&auth_url, &secrets.state, self.login)
+            .await?;
+
+        // Exchange the auth code for a token
+        let token_response = self
+            .exchange_code_for_token(auth_code, &redirect_uri, &secrets.code_verifier)
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Error exchanging code for token: {}", e),
+            })?;
+
+        // Store the token response
+        TokenService::write_token_to_file(&auth_file_path, &token_response).map_err(|e| {
+            KindleError {
+                message: format!("Error writing token to file: {}", e),
+            }
+        })?;
+
+        Ok(token_response.access_token)
+    }
+
+    /// Exchange an authorization code for an access token
+    ///
+    /// # Arguments
+    ///
+    /// * `auth_code` - Authorization code received from the OAuth redirect
+    /// * `redirect_uri` - The redirect URI used in the initial authorization request
+    /// * `code_verifier` - The PKCE verifier of the authorization request
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TokenResponse, Box<dyn Error>>` - Token response or an error
+    async fn exchange_code_for_token(
+        &self,
+        auth_code: String,
+        redirect_uri: &str,
+        code_verifier: &str,
+    ) -> Result<TokenResponse, Box<dyn Error>> {
+        let params = [
+            ("client_id", self.client_id),
+            ("client_secret", self.client_secret),
+            ("code", auth_code.as_str()),
+            ("redirect_uri", redirect_uri),
+            ("grant_type", "authorization_code"),
+            ("code_verifier", code_verifier),
+        ];
+
+        let mut res: TokenResponse = self
+            .client
+            .post(GOOGLE_TOKEN_URL)
+            .form(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Calculate the expiration time
+        res.expires_at = Some(Utc::now().timestamp() + res.expires_in as i64);
+
+        Ok(res)
+    }
+
+    /// Refresh an access token using a refresh token
+    ///
+    /// # Arguments
+    ///
+    /// * `refresh_token` - The refresh token to use
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TokenResponse, Box<dyn Error>>` - New token response or an error
+    async fn refresh_access_token(
+        &self,
+        refresh_token: &str,
+    ) -> Result<TokenResponse, Box<dyn Error>> {
+        let params = [
+            ("client_id", self.client_id),
+            ("client_secret", self.client_secret),
+            ("refresh_token", refresh_token),
+            ("grant_type", "refresh_token"),
+        ];
+
+        let mut res: TokenResponse = self
+            .client
+            .post(GOOGLE_TOKEN_URL)
+            .form(&params)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Calculate the expiration time
+        res.expires_at = Some(Utc::now().timestamp() + res.expires_in as i64);
+
+        Ok(res)
+    }
+}
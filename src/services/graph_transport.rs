@@ -0,0 +1,877 @@
+//! # Microsoft Graph Transport
+//!
+//! This module delivers emails through the Microsoft Graph API, using `sendMail` for
+//! small attachments and draft messages with upload sessions for larger ones.
+
+use std::time::Duration;
+
+use chrono::{DateTime, SecondsFormat};
+use log::{info, warn};
+use reqwest::Client;
+
+use crate::models::{
+    Attachment, AttachmentItem, Body, DraftMessage, Email, EmailAddress, InboxMessageList,
+    IncomingMail, InternetMessageHeader, KindleError, MailAttachment, MailFolder, MailFolderList,
+    MailFolderRequest, Message, MessageLocation, MoveRequest, OutgoingMail, Recipient,
+    SentItemsCleanup, UploadSession, UploadSessionRequest,
+};
+use crate::services::{AzureService, MailTransport, ReplyService, RetryService, StreamService};
+
+/// Largest total attachment size that can be sent inline with `sendMail` (3 MB)
+const INLINE_ATTACHMENT_LIMIT: u64 = 3 * 1024 * 1024;
+
+/// Size of each chunk uploaded through an upload session
+///
+/// The Graph API requires chunks to be a multiple of 320 KiB and smaller than 4 MB.
+const UPLOAD_CHUNK_SIZE: usize = 10 * 320 * 1024;
+
+/// Number of checks that a sent message reached the Sent Items folder before its cleanup
+const SENT_CHECK_ATTEMPTS: u32 = 10;
+
+/// Delay between two checks that a sent message reached the Sent Items folder
+const SENT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Largest number of received messages fetched when looking for the replies of Amazon
+const INBOX_PAGE_SIZE: &str = "50";
+
+/// Mail transport sending emails with the Microsoft Graph API
+pub struct GraphTransport<'a> {
+    /// Azure service used to obtain the access token
+    pub azure_service: AzureService<'a>,
+    /// Shared HTTP client used for the Graph API requests
+    pub client: &'a Client,
+    /// Retry policy applied to the Graph API requests
+    pub retry_service: RetryService<'a>,
+    /// Microsoft Graph URL of the mailbox sending the emails
+    mailbox_url: String,
+    /// Access token obtained while preparing the transport
+    access_token: Option<String>,
+    /// Identifier of the Sent Items folder, resolved when the sent emails are cleaned up
+    sent_items_folder_id: Option<String>,
+    /// Identifier of the folder the sent emails are moved to by the `move` cleanup
+    cleanup_folder_id: Option<String>,
+}
+
+impl<'a> GraphTransport<'a> {
+    /// Create a new instance of GraphTransport
+    ///
+    /// # Arguments
+    ///
+    /// * `azure_service` - The Azure service for authentication
+    /// * `client` - The shared HTTP client
+    /// * `retry_service` - The retry policy applied to the Graph API requests
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new GraphTransport instance
+    pub fn new(
+        azure_service: AzureService<'a>,
+        client: &'a Client,
+        retry_service: RetryService<'a>,
+    ) -> Self {
+        GraphTransport {
+            mailbox_url: format!(
+                "{}/v1.0{}",
+                azure_service.config.graph_endpoint(),
+                azure_service.config.mailbox_path()
+            ),
+            azure_service,
+            client,
+            retry_service,
+            access_token: None,
+            sent_items_folder_id: None,
+            cleanup_folder_id: None,
+        }
+    }
+
+    /// Get the access token obtained while preparing the transport
+    ///
+    /// # Returns
+    ///
+    /// * `Result<&str, KindleError>` - The access token or an error
+    fn access_token(&self) -> Result<&str, KindleError> {
+        self.access_token.as_deref().ok_or_else(|| KindleError {
+            message: "Graph transport used before authentication".to_string(),
+        })
+    }
+
+    /// Send an email with inline attachments using `sendMail`
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The email to send
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn send_inline(&self, mail: &OutgoingMail) -> Result<(), KindleError> {
+        // The attachment contents are streamed from disk in place of the placeholders
+        let attachments = mail
+            .attachments
+            .iter()
+            .enumerate()
+            .map(|(index, attachment)| Self::build_attachment(attachment, index))
+            .collect();
+
+        // Create email payload
+        let email_payload = Email {
+            message: self.build_message(mail, attachments),
+            save_to_sent_items: self.azure_service.config.save_to_sent_items,
+        };
+        let body = StreamService::json_body(&email_payload, &mail.attachments)?;
+
+        let access_token = self.access_token()?;
+        let response = self
+            .retry_service
+            .send_non_idempotent("sendMail", || {
+                self.client
+                    .post(format!("{}/sendMail", self.mailbox_url))
+                    .bearer_auth(access_token)
+                    .header("Content-Type", "application/json")
+                    .header("Content-Length", body.content_length)
+                    .body(body.body())
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to send email: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response("Failed to send email", response).await);
+        }
+
+        info!("Email with attachment sent successfully!");
+        Ok(())
+    }
+
+    /// Send an email by attaching its files to a draft message, using upload sessions
+    /// for the large ones
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The email to send
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - Identifier of the sent message or an error
+    async fn send_with_draft(&self, mail: &OutgoingMail) -> Result<String, KindleError> {
+        let access_token = self.access_token()?;
+
+        // Create a draft message without attachments. Its immutable identifier still
+        // designates the message once it is sent and moved to the Sent Items folder.
+        let draft_message = self.build_message(mail, Vec::new());
+        let response = self
+            .retry_service
+            .send_non_idempotent("Draft creation", || {
+                self.client
+                    .post(format!("{}/messages", self.mailbox_url))
+                    .bearer_auth(access_token)
+                    .header("Prefer", "IdType=\"ImmutableId\"")
+                    .json(&draft_message)
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to create draft message: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(
+                KindleError::from_response("Failed to create draft message", response).await,
+            );
+        }
+
+        let draft: DraftMessage = response.json().await.map_err(|e| KindleError {
+            message: format!("Failed to parse draft message: {}", e),
+        })?;
+
+        // A draft that could not be sent is deleted, so that it does not linger in Drafts
+        if let Err(e) = self.send_draft(&draft.id, mail).await {
+            self.delete_draft(&draft.id).await;
+            return Err(e);
+        }
+
+        info!("Email with attachment sent successfully!");
+        Ok(draft.id)
+    }
+
+    /// Attach the files of an email to its draft message and send it
+    ///
+    /// # Arguments
+    ///
+    /// * `draft_id` - Identifier of the draft message
+    /// * `mail` - The email to send
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn send_draft(&self, draft_id: &str, mail: &OutgoingMail) -> Result<(), KindleError> {
+        let access_token = self.access_token()?;
+
+        // Small attachments are added directly, larger ones through an upload session
+        for attachment in &mail.attachments {
+            if attachment.size <= INLINE_ATTACHMENT_LIMIT {
+                self.add_attachment(draft_id, attachment).await?;
+            } else {
+                self.upload_attachment(draft_id, attachment).await?;
+            }
+        }
+
+        // Send the draft
+        let response = self
+            .retry_service
+            .send_non_idempotent("Draft sending", || {
+                self.client
+                    .post(format!("{}/messages/{}/send", self.mailbox_url, draft_id))
+                    .bearer_auth(access_token)
+                    .header("Content-Length", 0)
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to send draft message: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response("Failed to send draft message", response).await);
+        }
+
+        Ok(())
+    }
+
+    /// Delete a draft message that could not be sent
+    ///
+    /// The deletion is best effort, a failure is only reported.
+    ///
+    /// # Arguments
+    ///
+    /// * `draft_id` - Identifier of the draft message
+    async fn delete_draft(&self, draft_id: &str) {
+        let Ok(access_token) = self.access_token() else {
+            return;
+        };
+
+        let response = self
+            .retry_service
+            .send("Draft deletion", || {
+                self.client
+                    .post(format!(
+                        "{}/messages/{}/permanentDelete",
+                        self.mailbox_url, draft_id
+                    ))
+                    .bearer_auth(access_token)
+                    .header("Content-Length", 0)
+            })
+            .await;
+
+        match response {
+            Ok(response) if response.status().is_success() => {
+                info!("Deleted the draft message that could not be sent");
+            }
+            Ok(response) => warn!(
+                "{}",
+                KindleError::from_response("Failed to delete draft message", response)
+                    .await
+                    .message
+            ),
+            Err(e) => warn!("Failed to delete draft message: {}", e),
+        }
+    }
+
+    /// Get the cleanup applied to the sent emails
+    ///
+    /// Emails sent through a draft are always saved in the Sent Items folder, so they
+    /// are deleted when no copy should be kept.
+    ///
+    /// # Returns
+    ///
+    /// * `SentItemsCleanup` - The cleanup of the emails sent through a draft
+    fn cleanup(&self) -> SentItemsCleanup {
+        let config = self.azure_service.config;
+        if config.save_to_sent_items {
+            config.sent_items_cleanup
+        } else {
+            SentItemsCleanup::Delete
+        }
+    }
+
+    /// Delete a sent message or move it to the cleanup folder
+    ///
+    /// # Arguments
+    ///
+    /// * `message_id` - Immutable identifier of the sent message
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn clean_up_sent_message(&self, message_id: &str) -> Result<(), KindleError> {
+        let cleanup = self.cleanup();
+        if cleanup == SentItemsCleanup::None {
+            return Ok(());
+        }
+        let access_token = self.access_token()?;
+
+        // Sending is asynchronous, the message must not be touched while in the Outbox
+        self.wait_until_sent(message_id).await?;
+
+        let message_url = format!("{}/messages/{}", self.mailbox_url, message_id);
+        let (operation, response) = match cleanup {
+            SentItemsCleanup::None => return Ok(()),
+            SentItemsCleanup::Delete => (
+                "Sent message deletion",
+                self.retry_service
+                    .send("Sent message deletion", || {
+                        self.client
+                            .post(format!("{}/permanentDelete", message_url))
+                            .bearer_auth(access_token)
+                            .header("Content-Length", 0)
+                    })
+                    .await,
+            ),
+            SentItemsCleanup::Move => {
+                let move_request = MoveRequest {
+                    destination_id: self.cleanup_folder_id.clone().ok_or_else(|| KindleError {
+                        message: "Cleanup folder used before being resolved".to_string(),
+                    })?,
+                };
+                (
+                    "Sent message move",
+                    self.retry_service
+                        .send("Sent message move", || {
+                            self.client
+                                .post(format!("{}/move", message_url))
+                                .bearer_auth(access_token)
+                                .json(&move_request)
+                        })
+                        .await,
+                )
+            }
+        };
+
+        let response = response.map_err(|e| KindleError {
+            message: format!("{} failed: {}", operation, e),
+        })?;
+        if !response.status().is_success() {
+            return Err(
+                KindleError::from_response(&format!("{} failed", operation), response).await,
+            );
+        }
+
+        info!("Cleaned up the sent message ({:?})", cleanup);
+        Ok(())
+    }
+
+    /// Wait until a sent message reaches the Sent Items folder
+    ///
+    /// # Arguments
+    ///
+    /// * `message_id` - Immutable identifier of the sent message
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success, or an error if the message is still not sent
+    async fn wait_until_sent(&self, message_id: &str) -> Result<(), KindleError> {
+        let access_token = self.access_token()?;
+        let sent_items_folder_id =
+            self.sent_items_folder_id
+                .as_deref()
+                .ok_or_else(|| KindleError {
+                    message: "Sent Items folder used before being resolved".to_string(),
+                })?;
+
+        for _ in 0..SENT_CHECK_ATTEMPTS {
+            let response = self
+                .retry_service
+                .send("Sent message lookup", || {
+                    self.client
+                        .get(format!("{}/messages/{}", self.mailbox_url, message_id))
+                        .query(&[("$select", "parentFolderId")])
+                        .bearer_auth(access_token)
+                        .header("Prefer", "IdType=\"ImmutableId\"")
+                })
+                .await
+                .map_err(|e| KindleError {
+                    message: format!("Failed to look up sent message: {}", e),
+                })?;
+
+            // The message may briefly be unavailable while it is being sent
+            if response.status().is_success() {
+                let location: MessageLocation = response.json().await.map_err(|e| KindleError {
+                    message: format!("Failed to parse sent message: {}", e),
+                })?;
+                if location.parent_folder_id == sent_items_folder_id {
+                    return Ok(());
+                }
+            } else if response.status() != reqwest::StatusCode::NOT_FOUND {
+                return Err(
+                    KindleError::from_response("Failed to look up sent message", response).await,
+                );
+            }
+
+            tokio::time::sleep(SENT_CHECK_INTERVAL).await;
+        }
+
+        Err(KindleError {
+            message: "Sent message did not reach the Sent Items folder in time".to_string(),
+        })
+    }
+
+    /// Get the identifier of a mail folder
+    ///
+    /// # Arguments
+    ///
+    /// * `folder` - Well-known name of the folder (e.g. "sentitems")
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - Identifier of the folder or an error
+    async fn folder_id(&self, folder: &str) -> Result<String, KindleError> {
+        let access_token = self.access_token()?;
+        let response = self
+            .retry_service
+            .send("Mail folder lookup", || {
+                self.client
+                    .get(format!("{}/mailFolders/{}", self.mailbox_url, folder))
+                    .bearer_auth(access_token)
+                    .header("Prefer", "IdType=\"ImmutableId\"")
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to look up mail folder {}: {}", folder, e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response(
+                &format!("Failed to look up mail folder {}", folder),
+                response,
+            )
+            .await);
+        }
+
+        let folder: MailFolder = response.json().await.map_err(|e| KindleError {
+            message: format!("Failed to parse mail folder: {}", e),
+        })?;
+        Ok(folder.id)
+    }
+
+    /// Get the identifier of the top-level mail folder with a given name, creating it
+    /// if needed
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the folder
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - Identifier of the folder or an error
+    async fn find_or_create_folder(&self, name: &str) -> Result<String, KindleError> {
+        let access_token = self.access_token()?;
+        let filter = format!("displayName eq '{}'", name.replace('\'', "''"));
+        let response = self
+            .retry_service
+            .send("Mail folder search", || {
+                self.client
+                    .get(format!("{}/mailFolders", self.mailbox_url))
+                    .query(&[("$filter", filter.as_str())])
+                    .bearer_auth(access_token)
+                    .header("Prefer", "IdType=\"ImmutableId\"")
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to search mail folder {}: {}", name, e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response(
+                &format!("Failed to search mail folder {}", name),
+                response,
+            )
+            .await);
+        }
+
+        let folders: MailFolderList = response.json().await.map_err(|e| KindleError {
+            message: format!("Failed to parse mail folders: {}", e),
+        })?;
+        if let Some(folder) = folders.value.into_iter().next() {
+            return Ok(folder.id);
+        }
+
+        info!("Creating mail folder {}", name);
+        let folder_request = MailFolderRequest {
+            display_name: name.to_string(),
+        };
+        let response = self
+            .retry_service
+            .send("Mail folder creation", || {
+                self.client
+                    .post(format!("{}/mailFolders", self.mailbox_url))
+                    .bearer_auth(access_token)
+                    .header("Prefer", "IdType=\"ImmutableId\"")
+                    .json(&folder_request)
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to create mail folder {}: {}", name, e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response(
+                &format!("Failed to create mail folder {}", name),
+                response,
+            )
+            .await);
+        }
+
+        let folder: MailFolder = response.json().await.map_err(|e| KindleError {
+            message: format!("Failed to parse mail folder: {}", e),
+        })?;
+        Ok(folder.id)
+    }
+
+    /// Add a small attachment to a draft message
+    ///
+    /// # Arguments
+    ///
+    /// * `message_id` - Identifier of the draft message
+    /// * `attachment` - The attachment to add
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn add_attachment(
+        &self,
+        message_id: &str,
+        attachment: &MailAttachment,
+    ) -> Result<(), KindleError> {
+        let access_token = self.access_token()?;
+        let payload = Self::build_attachment(attachment, 0);
+        let body = StreamService::json_body(&payload, std::slice::from_ref(attachment))?;
+
+        let response = self
+            .retry_service
+            .send_non_idempotent("Attachment creation", || {
+                self.client
+                    .post(format!(
+                        "{}/messages/{}/attachments",
+                        self.mailbox_url, message_id
+                    ))
+                    .bearer_auth(access_token)
+                    .header("Content-Type", "application/json")
+                    .header("Content-Length", body.content_length)
+                    .body(body.body())
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to add attachment: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(KindleError::from_response("Failed to add attachment", response).await);
+        }
+
+        Ok(())
+    }
+
+    /// Upload an attachment to a draft message through an upload session
+    ///
+    /// # Arguments
+    ///
+    /// * `message_id` - Identifier of the draft message
+    /// * `attachment` - The attachment to upload
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn upload_attachment(
+        &self,
+        message_id: &str,
+        attachment: &MailAttachment,
+    ) -> Result<(), KindleError> {
+        // Create the upload session for the attachment
+        let session_request = UploadSessionRequest {
+            attachment_item: AttachmentItem {
+                attachment_type: "file".to_string(),
+                name: attachment.name.clone(),
+                size: attachment.size,
+                content_type: attachment.content_type.clone(),
+            },
+        };
+
+        let access_token = self.access_token()?;
+        let response = self
+            .retry_service
+            .send("Upload session creation", || {
+                self.client
+                    .post(format!(
+                        "{}/messages/{}/attachments/createUploadSession",
+                        self.mailbox_url, message_id
+                    ))
+                    .bearer_auth(access_token)
+                    .json(&session_request)
+            })
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to create upload session: {}", e),
+            })?;
+
+        if !response.status().is_success() {
+            return Err(
+                KindleError::from_response("Failed to create upload session", response).await,
+            );
+        }
+
+        let session: UploadSession = response.json().await.map_err(|e| KindleError {
+            message: format!("Failed to parse upload session: {}", e),
+        })?;
+
+        // Upload the attachment chunk by chunk. The upload URL is pre-authenticated,
+        // so the access token must not be sent along with the chunks. Only one chunk
+        // is held in memory at a time.
+        let total_size = attachment.size;
+        let mut start = 0;
+        while start < total_size {
+            let length = (total_size - start).min(UPLOAD_CHUNK_SIZE as u64);
+            let end = start + length - 1;
+            let chunk = StreamService::read_chunk(&attachment.path, start, length as usize).await?;
+
+            let response = self
+                .retry_service
+                .send("Attachment chunk upload", || {
+                    self.client
+                        .put(&session.upload_url)
+                        .header("Content-Length", length)
+                        .header(
+                            "Content-Range",
+                            format!("bytes {}-{}/{}", start, end, total_size),
+                        )
+                        .body(chunk.clone())
+                })
+                .await
+                .map_err(|e| KindleError {
+                    message: format!("Failed to upload attachment chunk: {}", e),
+                })?;
+
+            if !response.status().is_success() {
+                return Err(KindleError::from_response(
+                    "Failed to upload attachment chunk",
+                    response,
+                )
+                .await);
+            }
+
+            info!(
+                "Uploaded {} of {} bytes of {}",
+                end + 1,
+                total_size,
+                attachment.name
+            );
+            start += length;
+        }
+
+        Ok(())
+    }
+
+    /// Build the Graph file attachment for an attachment, with a placeholder standing
+    /// for its content
+    ///
+    /// # Arguments
+    ///
+    /// * `attachment` - The attachment
+    /// * `index` - Index of the attachment in the list streamed with the payload
+    ///
+    /// # Returns
+    ///
+    /// * `Attachment` - The Graph file attachment
+    fn build_attachment(attachment: &MailAttachment, index: usize) -> Attachment {
+        Attachment {
+            odata_type: "#microsoft.graph.fileAttachment".to_string(),
+            name: attachment.name.clone(),
+            content_type: attachment.content_type.clone(),
+            content_bytes: StreamService::placeholder(index),
+        }
+    }
+
+    /// Build the Graph message for an email
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The email to send
+    /// * `attachments` - Attachments to include in the message
+    ///
+    /// # Returns
+    ///
+    /// * `Message` - The Graph message
+    fn build_message(&self, mail: &OutgoingMail, attachments: Vec<Attachment>) -> Message {
+        let config = self.azure_service.config;
+
+        Message {
+            subject: mail.subject.clone(),
+            body: Body {
+                content_type: "Text".to_string(),
+                content: mail.body.clone(),
+            },
+            to_recipients: Self::build_recipients(&mail.recipients),
+            from: config.from.as_deref().map(Self::build_recipient),
+            reply_to: Self::build_recipients(&config.reply_to),
+            internet_message_id: mail.message_id.clone(),
+            internet_message_headers: mail
+                .headers
+                .iter()
+                .map(|(name, value)| InternetMessageHeader {
+                    name: name.clone(),
+                    value: value.clone(),
+                })
+                .collect(),
+            attachments,
+        }
+    }
+
+    /// Build the Graph recipients for a list of addresses
+    ///
+    /// # Arguments
+    ///
+    /// * `addresses` - The email addresses
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Recipient>` - The Graph recipients
+    fn build_recipients(addresses: &[String]) -> Vec<Recipient> {
+        addresses
+            .iter()
+            .map(|address| Self::build_recipient(address))
+            .collect()
+    }
+
+    /// Build the Graph recipient for an address
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The email address
+    ///
+    /// # Returns
+    ///
+    /// * `Recipient` - The Graph recipient
+    fn build_recipient(address: &str) -> Recipient {
+        Recipient {
+            email_address: EmailAddress {
+                address: address.to_string(),
+            },
+        }
+    }
+}
+
+impl MailTransport for GraphTransport<'_> {
+    async fn prepare(&mut self) -> Result<(), KindleError> {
+        self.access_token = Some(self.azure_service.authenticate().await?);
+
+        // Resolve the folders used to clean up the sent emails
+        let cleanup = self.cleanup();
+        if cleanup != SentItemsCleanup::None {
+            self.sent_items_folder_id = Some(self.folder_id("sentitems").await?);
+        }
+        if cleanup == SentItemsCleanup::Move {
+            let folder = &self.azure_service.config.sent_items_folder;
+            self.cleanup_folder_id = Some(self.find_or_create_folder(folder).await?);
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_amazon_replies(&self, since: i64) -> Result<Vec<IncomingMail>, KindleError> {
+        let access_token = self.access_token()?;
+        let since = DateTime::from_timestamp(since, 0)
+            .unwrap_or_default()
+            .to_rfc3339_opts(SecondsFormat::Secs, true);
+        let filter = format!("receivedDateTime ge {}", since);
+
+        // The messages of the window are read page by page, following the next link
+        let mut messages = Vec::new();
+        let mut next_link: Option<String> = None;
+        loop {
+            let response = self
+                .retry_service
+                .send("Inbox check", || {
+                    let request = match &next_link {
+                        Some(link) => self.client.get(link),
+                        None => self
+                            .client
+                            .get(format!("{}/mailFolders/inbox/messages", self.mailbox_url))
+                            .query(&[
+                                ("$filter", filter.as_str()),
+                                ("$select", "subject,body,from,receivedDateTime"),
+                                ("$orderby", "receivedDateTime desc"),
+                                ("$top", INBOX_PAGE_SIZE),
+                            ]),
+                    };
+                    request
+                        .bearer_auth(access_token)
+                        .header("Prefer", "outlook.body-content-type=\"text\"")
+                })
+                .await
+                .map_err(|e| KindleError {
+                    message: format!("Failed to read the inbox: {}", e),
+                })?;
+
+            if !response.status().is_success() {
+                return Err(KindleError::from_response("Failed to read the inbox", response).await);
+            }
+
+            let page: InboxMessageList = response.json().await.map_err(|e| KindleError {
+                message: format!("Failed to parse the inbox messages: {}", e),
+            })?;
+            messages.extend(page.value);
+
+            match page.next_link {
+                Some(link) => next_link = Some(link),
+                None => break,
+            }
+        }
+
+        // Filtering on the sender is not supported together with the date filter
+        Ok(messages
+            .into_iter()
+            .filter_map(|message| {
+                let from = message.from?.email_address.address;
+                if !ReplyService::is_amazon_address(&from) {
+                    return None;
+                }
+
+                let received_at = DateTime::parse_from_rfc3339(&message.received_date_time)
+                    .map(|date| date.timestamp())
+                    .unwrap_or_default();
+                Some(IncomingMail {
+                    from,
+                    subject: message.subject.unwrap_or_default(),
+                    body: message.body.map(|body| body.content).unwrap_or_default(),
+                    received_at,
+                })
+            })
+            .collect())
+    }
+
+    async fn send(&self, mail: &OutgoingMail) -> Result<(), KindleError> {
+        // Only a message sent through a draft can be found again to be cleaned up, which
+        // is not needed when no copy is kept or the copy is left in Sent Items
+        let config = self.azure_service.config;
+        let needs_cleanup =
+            config.save_to_sent_items && config.sent_items_cleanup != SentItemsCleanup::None;
+        if mail.attachments_size() <= INLINE_ATTACHMENT_LIMIT && !needs_cleanup {
+            return self.send_inline(mail).await;
+        }
+
+        if mail.attachments_size() > INLINE_ATTACHMENT_LIMIT {
+            info!(
+                "Attachments are larger than {} bytes, using a draft message",
+                INLINE_ATTACHMENT_LIMIT
+            );
+        }
+        let message_id = self.send_with_draft(mail).await?;
+
+        // The email is delivered, a failed cleanup must not make it be sent again
+        if self.cleanup() != SentItemsCleanup::None
+            && let Err(e) = self.clean_up_sent_message(&message_id).await
+        {
+            warn!("Failed to clean up the sent message: {}", e.message);
+        }
+
+        Ok(())
+    }
+}
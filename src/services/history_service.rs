@@ -0,0 +1,36 @@
+//! # Send History Service
+//!
+//! This module persists the history of the recent sends on disk between runs.
+
+use crate::models::{KindleError, SendHistory};
+use crate::services::StateService;
+
+/// Name of the send history file in the application directory
+const HISTORY_FILE_NAME: &str = "history.json";
+
+/// Service for reading and writing the send history
+pub struct HistoryService {}
+
+impl HistoryService {
+    /// Read the send history, starting from an empty history if there is none yet
+    ///
+    /// # Returns
+    ///
+    /// * `Result<SendHistory, KindleError>` - The send history or an error
+    pub fn read_history() -> Result<SendHistory, KindleError> {
+        StateService::read(HISTORY_FILE_NAME, "send history")
+    }
+
+    /// Write the send history
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The send history to write
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    pub fn write_history(history: &SendHistory) -> Result<(), KindleError> {
+        StateService::write(HISTORY_FILE_NAME, "send history", history)
+    }
+}
@@ -0,0 +1,64 @@
+//! # HTTP Client Service
+//!
+//! This module builds the HTTP client shared by every service calling a web API, so
+//! that connections are reused and the timeouts, proxy and certificates configured
+//! once apply to all requests.
+
+use std::fs;
+use std::time::Duration;
+
+use log::info;
+use reqwest::{Certificate, Client, NoProxy, Proxy};
+
+use crate::models::{HttpConfig, KindleError};
+
+/// Service for building the shared HTTP client
+pub struct HttpService {}
+
+impl HttpService {
+    /// Build the HTTP client from the configuration
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - HTTP client settings
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Client, KindleError>` - The HTTP client or an error
+    pub fn build_client(config: &HttpConfig) -> Result<Client, KindleError> {
+        let mut builder = Client::builder()
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .read_timeout(Duration::from_secs(config.read_timeout_secs))
+            .user_agent(config.user_agent.as_str());
+
+        if let Some(proxy_url) = &config.proxy {
+            let proxy = Proxy::all(proxy_url).map_err(|e| KindleError {
+                message: format!("Invalid proxy URL {}: {}", proxy_url, e),
+            })?;
+            let no_proxy = NoProxy::from_string(&config.no_proxy.join(","));
+
+            info!("Using HTTP proxy {}", proxy_url);
+            builder = builder.proxy(proxy.no_proxy(no_proxy));
+        }
+
+        if let Some(ca_bundle) = &config.ca_bundle {
+            let pem = fs::read(ca_bundle).map_err(|e| KindleError {
+                message: format!("Failed to read CA bundle {}: {}", ca_bundle, e),
+            })?;
+            let certificates = Certificate::from_pem_bundle(&pem).map_err(|e| KindleError {
+                message: format!("Invalid CA bundle {}: {}", ca_bundle, e),
+            })?;
+
+            info!(
+                "Trusting {} extra root certificates from {}",
+                certificates.len(),
+                ca_bundle
+            );
+            builder = builder.tls_certs_merge(certificates);
+        }
+
+        builder.build().map_err(|e| KindleError {
+            message: format!("Failed to build HTTP client: {}", e),
+        })
+    }
+}
@@ -1,20 +1,47 @@
 //! # Kindle Email Service
 //!
-//! This module provides services for sending e-book files to Kindle devices via email
-//! using the Microsoft Graph API.
+//! This module provides services for composing the emails that carry e-book files
+//! to Kindle devices, independently of the transport used to deliver them.
 
-use base64::{Engine as _, engine::general_purpose};
-use log::info;
-use reqwest::Client;
-use std::fs::File;
-use std::io::Read;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
 
-use crate::models::{Attachment, Body, Email, EmailAddress, KindleError, Message, Recipient};
+use chrono::{Local, Utc};
+use rand::Rng;
+use sha2::{Digest, Sha256};
 
-/// Service for sending e-books to Kindle devices via email
+use crate::models::{KindleError, MailAttachment, MessageConfig, OutgoingMail};
+use crate::services::{FormatService, MetadataService, TemplateService};
+
+/// Largest document accepted by the Send-to-Kindle service (50 MB)
+const MAX_ATTACHMENT_SIZE: u64 = 50 * 1024 * 1024;
+
+/// MIME type used for files whose format is unknown
+const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Header identifying the run that sent an email
+const JOB_HEADER: &str = "X-Kindle-Sender-Job";
+
+/// Header listing the SHA-256 hashes of the files attached to an email
+const FILE_HASH_HEADER: &str = "X-Kindle-Sender-File-Hash";
+
+/// Hexadecimal digits kept of each hash in the file hash header, so that the header of
+/// an email with 25 attachments stays short. The full hashes are in the send history.
+const HEADER_HASH_LENGTH: usize = 16;
+
+/// Domain part of the Message-ID of the emails
+const MESSAGE_ID_DOMAIN: &str = "kindle-sender";
+
+/// Service for composing emails sent to Kindle devices
 pub struct KindleService<'a> {
     /// List of recipient email addresses (Kindle addresses)
     pub emails: &'a [String],
+    /// Templates of the subject and body of the emails
+    pub message: &'a MessageConfig,
+    /// Identifier of the run, put in the headers of its emails
+    pub job_id: String,
 }
 
 impl<'a> KindleService<'a> {
@@ -23,116 +50,328 @@ impl<'a> KindleService<'a> {
     /// # Arguments
     ///
     /// * `emails` - List of recipient email addresses (Kindle addresses)
+    /// * `message` - Templates of the subject and body of the emails
     ///
     /// # Returns
     ///
     /// * `Self` - A new KindleService instance
-    pub fn new(emails: &'a [String]) -> Self {
-        KindleService { emails }
+    pub fn new(emails: &'a [String], message: &'a MessageConfig) -> Self {
+        KindleService {
+            emails,
+            message,
+            job_id: format!(
+                "{}-{:08x}",
+                Utc::now().format("%Y%m%d%H%M%S"),
+                rand::rng().random::<u32>()
+            ),
+        }
+    }
+
+    /// Compose the email carrying files to Kindle devices
+    ///
+    /// The subject and body are rendered from the templates of the extension of the
+    /// files, or from the default ones when the files have different extensions.
+    /// Each email gets its own Message-ID, and headers naming the run and the hashes
+    /// of its files.
+    ///
+    /// # Arguments
+    ///
+    /// * `attachments` - The files to attach to the email
+    ///
+    /// # Returns
+    ///
+    /// * `OutgoingMail` - The email
+    pub fn compose_mail(&self, attachments: Vec<MailAttachment>) -> OutgoingMail {
+        let (subject, body) = self.templates(&attachments);
+        let values = Self::placeholder_values(&attachments);
+
+        // Line breaks coming from the metadata are not allowed in the subject
+        let subject = TemplateService::render(subject, &values)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let hashes = attachments
+            .iter()
+            .map(|attachment| {
+                let length = attachment.sha256.len().min(HEADER_HASH_LENGTH);
+                &attachment.sha256[..length]
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        OutgoingMail {
+            recipients: self.emails.to_vec(),
+            subject,
+            body: TemplateService::render(body, &values),
+            attachments,
+            message_id: Self::message_id(),
+            headers: vec![
+                (JOB_HEADER.to_string(), self.job_id.clone()),
+                (FILE_HASH_HEADER.to_string(), hashes),
+            ],
+        }
     }
 
-    /// Send a file to Kindle devices
+    /// Compose the email carrying files to a single Kindle device
     ///
     /// # Arguments
     ///
-    /// * `access_token` - Microsoft Graph API access token
-    /// * `file_path` - Path to the file to be sent
+    /// * `receiver` - Email address of the Kindle device
+    /// * `attachments` - The files to attach to the email
     ///
     /// # Returns
     ///
-    /// * `Result<(), KindleError>` - Success or an error
-    pub async fn send_file(
+    /// * `OutgoingMail` - The email
+    pub fn compose_mail_to(
         &self,
-        access_token: String,
-        file_path: &str,
-    ) -> Result<(), KindleError> {
-        let client = Client::new();
+        receiver: &str,
+        attachments: Vec<MailAttachment>,
+    ) -> OutgoingMail {
+        OutgoingMail {
+            recipients: vec![receiver.to_string()],
+            ..self.compose_mail(attachments)
+        }
+    }
 
-        // Read the file and encode it in base64
-        let mut file = File::open(file_path).map_err(|e| KindleError {
-            message: format!("Failed to open file: {}", e),
-        })?;
-        let mut buffer = Vec::new();
-        file.read_to_end(&mut buffer).map_err(|e| KindleError {
-            message: format!("Failed to read file: {}", e),
+    /// Generate a unique Message-ID
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The Message-ID, with its angle brackets
+    fn message_id() -> String {
+        format!(
+            "<{}.{:016x}@{}>",
+            Utc::now().timestamp_millis(),
+            rand::rng().random::<u64>(),
+            MESSAGE_ID_DOMAIN
+        )
+    }
+
+    /// Get the subject and body templates of an email
+    ///
+    /// # Arguments
+    ///
+    /// * `attachments` - The files attached to the email
+    ///
+    /// # Returns
+    ///
+    /// * `(&str, &str)` - The subject and body templates
+    fn templates(&self, attachments: &[MailAttachment]) -> (&'a str, &'a str) {
+        let message = self.message;
+        let mut keys = attachments
+            .iter()
+            .map(|attachment| message.override_key(&attachment.name));
+
+        // The override applies only if it is the one of every file
+        let message_override = match keys.next() {
+            Some(Some(first)) if keys.all(|key| key == Some(first)) => &message.overrides[first],
+            _ => return (&message.subject, &message.body),
+        };
+
+        (
+            message_override
+                .subject
+                .as_deref()
+                .unwrap_or(&message.subject),
+            message_override.body.as_deref().unwrap_or(&message.body),
+        )
+    }
+
+    /// Compute the values of the template placeholders for the files of an email
+    ///
+    /// The values of the files are joined with commas when there are several of them,
+    /// `{size}` is their total size.
+    ///
+    /// # Arguments
+    ///
+    /// * `attachments` - The files attached to the email
+    ///
+    /// # Returns
+    ///
+    /// * `BTreeMap<&str, String>` - The value of each placeholder
+    fn placeholder_values(attachments: &[MailAttachment]) -> BTreeMap<&'static str, String> {
+        let mut filenames = Vec::new();
+        let mut stems = Vec::new();
+        let mut extensions = Vec::new();
+        let mut titles = Vec::new();
+        let mut authors = Vec::new();
+
+        for attachment in attachments {
+            let path = Path::new(&attachment.name);
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_else(|| attachment.name.clone());
+            let extension = Self::extension(&attachment.name);
+            let metadata = MetadataService::read_metadata(attachment);
+
+            filenames.push(attachment.name.clone());
+            titles.push(metadata.title.unwrap_or_else(|| stem.clone()));
+            if let Some(author) = metadata.author
+                && !authors.contains(&author)
+            {
+                authors.push(author);
+            }
+            if !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+            stems.push(stem);
+        }
+
+        let size = attachments.iter().map(|attachment| attachment.size).sum();
+
+        BTreeMap::from([
+            ("filename", filenames.join(", ")),
+            ("stem", stems.join(", ")),
+            ("ext", extensions.join(", ")),
+            ("size", Self::format_size(size)),
+            ("date", Local::now().format("%Y-%m-%d").to_string()),
+            ("title", titles.join(", ")),
+            ("author", authors.join(", ")),
+        ])
+    }
+
+    /// Get the extension of a filename
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The filename
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The extension without the leading dot, empty if there is none
+    fn extension(name: &str) -> String {
+        Path::new(name)
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+
+    /// Format a size in bytes for humans
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The size in bytes
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The size in B, KB or MB
+    fn format_size(size: u64) -> String {
+        const KB: u64 = 1024;
+        const MB: u64 = 1024 * KB;
+
+        if size < KB {
+            format!("{} B", size)
+        } else if size < MB {
+            format!("{:.1} KB", size as f64 / KB as f64)
+        } else {
+            format!("{:.1} MB", size as f64 / MB as f64)
+        }
+    }
+
+    /// Describe a file as an email attachment
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file to attach
+    ///
+    /// # Returns
+    ///
+    /// * `Result<MailAttachment, KindleError>` - The attachment or an error
+    pub fn build_attachment(&self, file_path: &str) -> Result<MailAttachment, KindleError> {
+        let metadata = fs::metadata(file_path).map_err(|e| KindleError {
+            message: format!("Failed to read file metadata: {}", e),
         })?;
-        let content_bytes = general_purpose::STANDARD.encode(&buffer);
 
-        let filename = file_path
-            .split('/')
-            .next_back()
+        let size = metadata.len();
+        if size > MAX_ATTACHMENT_SIZE {
+            return Err(KindleError {
+                message: format!(
+                    "File is too large to be sent to Kindle: {} bytes (limit is {} bytes)",
+                    size, MAX_ATTACHMENT_SIZE
+                ),
+            });
+        }
+
+        let name = Path::new(file_path)
+            .file_name()
             .ok_or_else(|| KindleError {
                 message: "Failed to get filename from file path".to_string(),
             })?
+            .to_string_lossy()
             .to_string();
 
-        // Create recipients list
-        let to_recipients = self
-            .emails
-            .iter()
-            .map(|email| Recipient {
-                email_address: EmailAddress {
-                    address: email.to_string(),
-                },
-            })
-            .collect();
-
-        // Create attachment
-        let attachment = Attachment {
-            odata_type: "#microsoft.graph.fileAttachment".to_string(),
-            name: filename,
-            content_type: "application/octet-stream".to_string(),
-            content_bytes,
-        };
+        let format = FormatService::identify_format(file_path)?;
+        let sha256 = Self::file_hash(file_path)?;
 
-        // Create email message
-        let message = Message {
-            subject: "Your Kindle File".to_string(),
-            body: Body {
-                content_type: "Text".to_string(),
-                content: "".to_string(),
-            },
-            to_recipients,
-            attachments: vec![attachment],
-        };
+        Ok(MailAttachment {
+            path: file_path.to_string(),
+            name,
+            content_type: format
+                .map(|format| format.content_type())
+                .unwrap_or(DEFAULT_CONTENT_TYPE)
+                .to_string(),
+            format,
+            size,
+            sha256,
+        })
+    }
 
-        // Create email payload
-        let email_payload = Email {
-            message,
-            save_to_sent_items: true,
-        };
+    /// Compute the SHA-256 hash of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - The hash in hexadecimal or an error
+    fn file_hash(file_path: &str) -> Result<String, KindleError> {
+        let mut file = File::open(file_path).map_err(|e| KindleError {
+            message: format!("Failed to open file: {}", e),
+        })?;
 
-        let response = client
-            .post("https://graph.microsoft.com/v1.0/me/sendMail")
-            .bearer_auth(&access_token)
-            .header("Content-Type", "application/json")
-            .json(&email_payload)
-            .send()
-            .await
-            .map_err(|e| KindleError {
-                message: format!("Failed to send email: {}", e),
-            })?;
-
-        if response.status().is_success() {
-            info!("Email with attachment sent successfully!");
-            Ok(())
-        } else {
-            let response_status = response.status();
-            let message = String::from_utf8(
-                response
-                    .bytes()
-                    .await
-                    .map_err(|e| KindleError {
-                        message: format!("Failed to read response: {}", e),
-                    })?
-                    .to_vec(),
-            )
-            .map_err(|e| KindleError {
-                message: format!("Failed to convert response to string: {}", e),
-            })?;
-            Err(KindleError {
-                message: format!("Failed to send email: {:?} {:?}", response_status, message),
-            })
+        let mut hasher = Sha256::new();
+        io::copy(&mut file, &mut hasher).map_err(|e| KindleError {
+            message: format!("Failed to read file: {}", e),
+        })?;
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attachment(index: usize) -> MailAttachment {
+        MailAttachment {
+            path: format!("{}.epub", index),
+            name: format!("{}.epub", index),
+            content_type: String::from("application/epub+zip"),
+            format: None,
+            size: 1,
+            sha256: format!("{:064x}", index),
         }
     }
+
+    #[test]
+    fn compose_mail_bounds_file_hash_header() {
+        let emails = [String::from("me@kindle.com")];
+        let message = MessageConfig::default();
+        let service = KindleService::new(&emails, &message);
+
+        let mail = service.compose_mail((0..25).map(attachment).collect());
+        let hashes = mail
+            .headers
+            .iter()
+            .find(|(name, _)| name == FILE_HASH_HEADER)
+            .map(|(_, value)| value.as_str())
+            .unwrap();
+
+        let hashes: Vec<&str> = hashes.split(", ").collect();
+        assert_eq!(hashes.len(), 25);
+        assert_eq!(hashes[24], &attachment(24).sha256[..HEADER_HASH_LENGTH]);
+        assert!(mail.headers.iter().all(|(_, value)| value.len() < 500));
+    }
 }
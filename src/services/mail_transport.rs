@@ -0,0 +1,47 @@
+//! # Mail Transport
+//!
+//! This module defines the interface implemented by every backend able to deliver
+//! e-books to Kindle devices.
+
+use crate::models::{IncomingMail, KindleError, OutgoingMail};
+
+/// Backend delivering emails to Kindle devices
+pub trait MailTransport {
+    /// Prepare the transport before any email is sent
+    ///
+    /// Transports use this step to authenticate or check that their server is reachable,
+    /// so that configuration problems are reported before the files are processed.
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn prepare(&mut self) -> Result<(), KindleError>;
+
+    /// Deliver an email
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The email to deliver
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    async fn send(&self, mail: &OutgoingMail) -> Result<(), KindleError>;
+
+    /// Fetch the emails received from Amazon by the sender
+    ///
+    /// Only transports with access to the mailbox of the sender support this.
+    ///
+    /// # Arguments
+    ///
+    /// * `since` - Timestamp of the oldest email to fetch
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<IncomingMail>, KindleError>` - The emails or an error
+    async fn fetch_amazon_replies(&self, _since: i64) -> Result<Vec<IncomingMail>, KindleError> {
+        Err(KindleError {
+            message: "This transport cannot read the replies of Amazon".to_string(),
+        })
+    }
+}
@@ -0,0 +1,207 @@
+//! # Book Metadata Service
+//!
+//! This module reads the title and author of e-books from the metadata embedded in
+//! EPUB books and Word documents.
+
+use std::fs::File;
+use std::io::Read;
+
+use log::debug;
+use zip::ZipArchive;
+
+use crate::models::{BookMetadata, DocumentFormat, KindleError, MailAttachment};
+
+/// Path of the container file pointing to the package document of an EPUB book
+const EPUB_CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// Path of the core properties of a Word document
+const DOCX_CORE_PATH: &str = "docProps/core.xml";
+
+/// Service for reading the metadata of e-books
+pub struct MetadataService {}
+
+impl MetadataService {
+    /// Read the metadata of an attachment
+    ///
+    /// Metadata that cannot be read is left empty, the failure is only logged.
+    ///
+    /// # Arguments
+    ///
+    /// * `attachment` - The attachment
+    ///
+    /// # Returns
+    ///
+    /// * `BookMetadata` - The metadata found in the file
+    pub fn read_metadata(attachment: &MailAttachment) -> BookMetadata {
+        let result = match attachment.format {
+            Some(DocumentFormat::Epub) => Self::read_epub_metadata(&attachment.path),
+            Some(DocumentFormat::Docx) => Self::read_docx_metadata(&attachment.path),
+            _ => Ok(BookMetadata::default()),
+        };
+
+        result.unwrap_or_else(|e| {
+            debug!(
+                "Failed to read metadata of {}: {}",
+                attachment.name, e.message
+            );
+            BookMetadata::default()
+        })
+    }
+
+    /// Read the metadata of an EPUB book from its package document
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the book
+    ///
+    /// # Returns
+    ///
+    /// * `Result<BookMetadata, KindleError>` - The metadata or an error
+    fn read_epub_metadata(file_path: &str) -> Result<BookMetadata, KindleError> {
+        let mut archive = Self::open_archive(file_path)?;
+
+        let container = Self::read_entry(&mut archive, EPUB_CONTAINER_PATH)?;
+        let package_path =
+            Self::attribute_value(&container, "full-path").ok_or_else(|| KindleError {
+                message: "No package document in the EPUB container".to_string(),
+            })?;
+        let package = Self::read_entry(&mut archive, &package_path)?;
+
+        Ok(BookMetadata {
+            title: Self::element_text(&package, "dc:title"),
+            author: Self::element_text(&package, "dc:creator"),
+        })
+    }
+
+    /// Read the metadata of a Word document from its core properties
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the document
+    ///
+    /// # Returns
+    ///
+    /// * `Result<BookMetadata, KindleError>` - The metadata or an error
+    fn read_docx_metadata(file_path: &str) -> Result<BookMetadata, KindleError> {
+        let mut archive = Self::open_archive(file_path)?;
+        let core = Self::read_entry(&mut archive, DOCX_CORE_PATH)?;
+
+        Ok(BookMetadata {
+            title: Self::element_text(&core, "dc:title"),
+            author: Self::element_text(&core, "dc:creator"),
+        })
+    }
+
+    /// Open a ZIP archive
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the archive
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ZipArchive<File>, KindleError>` - The archive or an error
+    fn open_archive(file_path: &str) -> Result<ZipArchive<File>, KindleError> {
+        let file = File::open(file_path).map_err(|e| KindleError {
+            message: format!("Failed to open file: {}", e),
+        })?;
+        ZipArchive::new(file).map_err(|e| KindleError {
+            message: format!("Failed to read archive: {}", e),
+        })
+    }
+
+    /// Read a text entry of a ZIP archive
+    ///
+    /// # Arguments
+    ///
+    /// * `archive` - The archive
+    /// * `name` - Path of the entry in the archive
+    ///
+    /// # Returns
+    ///
+    /// * `Result<String, KindleError>` - Content of the entry or an error
+    fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<String, KindleError> {
+        let mut entry = archive.by_name(name).map_err(|e| KindleError {
+            message: format!("Failed to find {} in archive: {}", name, e),
+        })?;
+
+        let mut content = String::new();
+        entry
+            .read_to_string(&mut content)
+            .map_err(|e| KindleError {
+                message: format!("Failed to read {} in archive: {}", name, e),
+            })?;
+        Ok(content)
+    }
+
+    /// Get the value of the first occurrence of an XML attribute
+    ///
+    /// # Arguments
+    ///
+    /// * `xml` - The XML document
+    /// * `name` - Name of the attribute
+    ///
+    /// # Returns
+    ///
+    /// * `Option<String>` - The unescaped value, or None if the attribute is missing
+    fn attribute_value(xml: &str, name: &str) -> Option<String> {
+        let start = xml.find(&format!("{}=\"", name))? + name.len() + 2;
+        let end = start + xml[start..].find('"')?;
+        Some(Self::unescape(&xml[start..end]))
+    }
+
+    /// Get the text of the first non-empty occurrence of an XML element
+    ///
+    /// # Arguments
+    ///
+    /// * `xml` - The XML document
+    /// * `name` - Qualified name of the element
+    ///
+    /// # Returns
+    ///
+    /// * `Option<String>` - The unescaped and trimmed text, or None if there is none
+    fn element_text(xml: &str, name: &str) -> Option<String> {
+        let open_tag = format!("<{}", name);
+        let close_tag = format!("</{}>", name);
+
+        let mut rest = xml;
+        while let Some(position) = rest.find(&open_tag) {
+            rest = &rest[position + open_tag.len()..];
+
+            // Skip elements whose name only starts with the searched one
+            if !rest.starts_with(['>', ' ', '\t', '\r', '\n']) {
+                continue;
+            }
+
+            let content_start = rest.find('>')? + 1;
+            let content_end = rest.find(&close_tag)?;
+            if content_end < content_start {
+                continue;
+            }
+
+            let text = Self::unescape(rest[content_start..content_end].trim());
+            if !text.is_empty() {
+                return Some(text);
+            }
+        }
+
+        None
+    }
+
+    /// Replace the predefined XML entities by the characters they stand for
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The escaped text
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The unescaped text
+    fn unescape(text: &str) -> String {
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&")
+    }
+}
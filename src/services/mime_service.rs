@@ -0,0 +1,259 @@
+//! # MIME Service
+//!
+//! This module renders outgoing emails as multipart MIME messages for the transports
+//! that deliver raw RFC 822 messages.
+
+use std::fs;
+
+use lettre::Message;
+use lettre::message::header::{ContentType, HeaderName, HeaderValue};
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+
+use crate::models::{KindleError, OutgoingMail};
+
+/// Service for rendering emails as MIME messages
+pub struct MimeService {}
+
+impl MimeService {
+    /// Build a multipart MIME message from an outgoing email
+    ///
+    /// The message contains a plain text part with the body, followed by one part
+    /// per attachment.
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The email to render
+    /// * `from` - Sender address of the message
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Message, KindleError>` - The MIME message or an error
+    pub fn build_message(mail: &OutgoingMail, from: &str) -> Result<Message, KindleError> {
+        let mut builder = Message::builder()
+            .from(Self::parse_mailbox(from)?)
+            .subject(mail.subject.clone())
+            .message_id(Some(mail.message_id.clone()));
+
+        for recipient in &mail.recipients {
+            builder = builder.to(Self::parse_mailbox(recipient)?);
+        }
+
+        for (name, value) in &mail.headers {
+            let header_name =
+                HeaderName::new_from_ascii(name.clone()).map_err(|_| KindleError {
+                    message: format!("Invalid header name {}", name),
+                })?;
+            builder = builder.raw_header(HeaderValue::new(header_name, value.clone()));
+        }
+
+        let mut multipart = MultiPart::mixed().singlepart(SinglePart::plain(mail.body.clone()));
+
+        for attachment in &mail.attachments {
+            let content = fs::read(&attachment.path).map_err(|e| KindleError {
+                message: format!("Failed to read file: {}", e),
+            })?;
+            let content_type =
+                ContentType::parse(&attachment.content_type).map_err(|e| KindleError {
+                    message: format!("Invalid content type {}: {}", attachment.content_type, e),
+                })?;
+            multipart = multipart
+                .singlepart(Attachment::new(attachment.name.clone()).body(content, content_type));
+        }
+
+        builder.multipart(multipart).map_err(|e| KindleError {
+            message: format!("Failed to build MIME message: {}", e),
+        })
+    }
+
+    /// Parse an email address
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The address to parse, optionally with a display name
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Mailbox, KindleError>` - The parsed mailbox or an error
+    fn parse_mailbox(address: &str) -> Result<Mailbox, KindleError> {
+        address.parse().map_err(|e| KindleError {
+            message: format!("Invalid email address {}: {}", address, e),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use base64::{Engine as _, engine::general_purpose};
+
+    use super::*;
+    use crate::models::MailAttachment;
+
+    /// A MIME part, with its unfolded headers
+    struct Part {
+        headers: String,
+        content: String,
+    }
+
+    /// Write a test file whose bytes depend on their position
+    fn write_file(name: &str, size: usize) -> (PathBuf, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!(
+            "kindle-sender-mime-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let content: Vec<u8> = (0..size).map(|i| (i * 13 % 256) as u8).collect();
+        fs::write(&path, &content).unwrap();
+        (path, content)
+    }
+
+    fn mail(attachments: &[(&PathBuf, &str)]) -> OutgoingMail {
+        OutgoingMail {
+            recipients: vec![
+                String::from("first@kindle.com"),
+                String::from("second@kindle.com"),
+            ],
+            subject: String::from("Books"),
+            body: String::from("Sent by kindle-sender"),
+            attachments: attachments
+                .iter()
+                .map(|(path, name)| MailAttachment {
+                    path: path.to_string_lossy().to_string(),
+                    name: name.to_string(),
+                    content_type: String::from("application/epub+zip"),
+                    format: None,
+                    size: 0,
+                    sha256: String::new(),
+                })
+                .collect(),
+            message_id: String::from("<run-1@kindle-sender.local>"),
+            headers: vec![
+                (String::from("X-Kindle-Sender-Job"), String::from("run-1")),
+                (
+                    String::from("X-Kindle-Sender-File-Hash"),
+                    String::from("0123abcd"),
+                ),
+            ],
+        }
+    }
+
+    /// Render a message and split it into its unfolded headers and its parts
+    fn render(mail: &OutgoingMail) -> (String, Vec<Part>, String) {
+        let message = MimeService::build_message(mail, "Sender <sender@example.com>").unwrap();
+        let formatted = String::from_utf8(message.formatted()).unwrap();
+        let (headers, body) = formatted.split_once("\r\n\r\n").unwrap();
+        let headers = headers.replace("\r\n ", " ");
+
+        let boundary = headers
+            .split_once("boundary=\"")
+            .and_then(|(_, rest)| rest.split_once('"'))
+            .map(|(boundary, _)| boundary.to_string())
+            .unwrap();
+        let delimiter = format!("--{}", boundary);
+        let (parts, epilogue) = body.rsplit_once(&format!("{}--", delimiter)).unwrap();
+        assert!(epilogue.trim().is_empty());
+
+        let parts = parts
+            .split(&delimiter)
+            .skip(1)
+            .map(|part| {
+                let (headers, content) = part
+                    .trim_start_matches("\r\n")
+                    .split_once("\r\n\r\n")
+                    .unwrap();
+                Part {
+                    headers: headers.replace("\r\n ", " "),
+                    content: content.trim_end_matches("\r\n").to_string(),
+                }
+            })
+            .collect();
+        (headers, parts, boundary)
+    }
+
+    /// Read back a filename encoded with RFC 2231 continuations
+    fn decode_filename(headers: &str) -> String {
+        let disposition = headers
+            .split("\r\n")
+            .find(|header| header.starts_with("Content-Disposition:"))
+            .unwrap();
+        let encoded: String = disposition
+            .split(';')
+            .filter_map(|parameter| parameter.trim().strip_prefix("filename*"))
+            .map(|parameter| parameter.split_once("*=").unwrap().1.trim_end())
+            .collect();
+        let encoded = encoded.strip_prefix("utf-8''").unwrap();
+
+        let mut bytes = Vec::new();
+        let mut chars = encoded.bytes();
+        while let Some(byte) = chars.next() {
+            if byte == b'%' {
+                let hex = [chars.next().unwrap(), chars.next().unwrap()];
+                bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).unwrap(), 16).unwrap());
+            } else {
+                bytes.push(byte);
+            }
+        }
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn build_message_writes_headers() {
+        let (headers, parts, _) = render(&mail(&[]));
+
+        assert!(headers.contains("From: Sender <sender@example.com>\r\n"));
+        assert!(headers.contains("To: first@kindle.com, second@kindle.com\r\n"));
+        assert!(headers.contains("Subject: Books\r\n"));
+        assert!(headers.contains("Message-ID: <run-1@kindle-sender.local>\r\n"));
+        assert!(headers.contains("X-Kindle-Sender-Job: run-1\r\n"));
+        assert!(headers.contains("X-Kindle-Sender-File-Hash: 0123abcd\r\n"));
+        assert_eq!(parts.len(), 1);
+        assert!(parts[0].headers.contains("Content-Type: text/plain"));
+        assert_eq!(parts[0].content, "Sent by kindle-sender");
+    }
+
+    #[test]
+    fn build_message_wraps_attachments_in_base64_lines() {
+        let (first_path, first) = write_file("first.epub", 1000);
+        let (second_path, second) = write_file("second.epub", 58);
+        let mail = mail(&[(&first_path, "first.epub"), (&second_path, "second.epub")]);
+
+        let (_, parts, boundary) = render(&mail);
+        fs::remove_file(first_path).unwrap();
+        fs::remove_file(second_path).unwrap();
+
+        assert_eq!(parts.len(), 3);
+        for (part, content) in parts[1..].iter().zip([first, second]) {
+            assert!(part.headers.contains("Content-Transfer-Encoding: base64"));
+            assert!(!part.content.contains(&boundary));
+            let lines: Vec<&str> = part.content.split("\r\n").collect();
+            assert!(lines.iter().all(|line| line.len() <= 76));
+            let decoded = general_purpose::STANDARD.decode(lines.concat()).unwrap();
+            assert_eq!(decoded, content);
+        }
+    }
+
+    #[test]
+    fn build_message_encodes_filenames() {
+        let (path, _) = write_file("names.epub", 10);
+        let name = "Caf\u{e9} \u{e9}t\u{e9}, \"vol\" 1.epub";
+        let mail = mail(&[(&path, "plain.epub"), (&path, name)]);
+
+        let (_, parts, _) = render(&mail);
+        fs::remove_file(path).unwrap();
+
+        assert!(parts[1].headers.contains("filename=\"plain.epub\""));
+        assert!(parts[2].headers.is_ascii());
+        assert_eq!(decode_filename(&parts[2].headers), name);
+    }
+
+    #[test]
+    fn build_message_rejects_invalid_header_name() {
+        let mut mail = mail(&[]);
+        mail.headers
+            .push((String::from("X-Bad Header"), String::from("value")));
+
+        let error = MimeService::build_message(&mail, "sender@example.com").unwrap_err();
+        assert!(error.message.contains("X-Bad Header"));
+    }
+}
@@ -3,14 +3,62 @@
 //! This module contains all the service components that provide
 //! the core functionality of the application.
 
+mod assertion_service;
 mod azure_service;
+mod browser_service;
+mod callback_service;
 mod config_service;
+mod delivery_service;
+mod eml_transport;
 mod file_service;
+mod format_service;
+mod gmail_transport;
+mod google_service;
+mod graph_transport;
+mod history_service;
+mod http_service;
 mod kindle_service;
+mod mail_transport;
+mod metadata_service;
+mod mime_service;
+mod pkce_service;
+mod quota_service;
+mod reply_service;
+mod retry_service;
 mod send_service;
+mod sendmail_transport;
+mod smtp_transport;
+mod state_service;
+mod stream_service;
+mod template_service;
+mod token_service;
 
+pub use assertion_service::AssertionService;
 pub use azure_service::AzureService;
+pub use browser_service::BrowserService;
+pub use callback_service::CallbackService;
 pub use config_service::ConfigService;
+pub use delivery_service::DeliveryService;
+pub use eml_transport::EmlTransport;
 pub use file_service::FileService;
+pub use format_service::FormatService;
+pub use gmail_transport::GmailTransport;
+pub use google_service::GoogleService;
+pub use graph_transport::GraphTransport;
+pub use history_service::HistoryService;
+pub use http_service::HttpService;
 pub use kindle_service::KindleService;
+pub use mail_transport::MailTransport;
+pub use metadata_service::MetadataService;
+pub use mime_service::MimeService;
+pub use pkce_service::PkceService;
+pub use quota_service::QuotaService;
+pub use reply_service::ReplyService;
+pub use retry_service::RetryService;
 pub use send_service::SendService;
+pub use sendmail_transport::SendmailTransport;
+pub use smtp_transport::SmtpTransport;
+pub use state_service::StateService;
+pub use stream_service::StreamService;
+pub use template_service::TemplateService;
+pub use token_service::TokenService;
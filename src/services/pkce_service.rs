@@ -0,0 +1,54 @@
+//! # PKCE Service
+//!
+//! This module generates the state and the PKCE (RFC 7636) verifier and challenge of
+//! the authorization requests, so that only the callback of the request started by
+//! the tool can complete a sign-in.
+
+use base64::{Engine as _, engine::general_purpose};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+
+use crate::models::AuthorizationSecrets;
+
+/// Number of random bytes of the PKCE verifier (43 characters once encoded)
+const VERIFIER_BYTES: usize = 32;
+
+/// Number of random bytes of the state
+const STATE_BYTES: usize = 16;
+
+/// Service for generating the secrets of authorization requests
+pub struct PkceService {}
+
+impl PkceService {
+    /// Generate the secrets of a new authorization request
+    ///
+    /// # Returns
+    ///
+    /// * `AuthorizationSecrets` - The state and the PKCE verifier and challenge
+    pub fn generate() -> AuthorizationSecrets {
+        let code_verifier = Self::random_string(VERIFIER_BYTES);
+        let code_challenge =
+            general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+
+        AuthorizationSecrets {
+            state: Self::random_string(STATE_BYTES),
+            code_verifier,
+            code_challenge,
+        }
+    }
+
+    /// Generate a random URL-safe string
+    ///
+    /// # Arguments
+    ///
+    /// * `length` - Number of random bytes
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The bytes encoded in unpadded base64url
+    fn random_string(length: usize) -> String {
+        let mut bytes = vec![0u8; length];
+        rand::rng().fill(bytes.as_mut_slice());
+        general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+}
@@ -0,0 +1,170 @@
+//! # Quota Service
+//!
+//! This module keeps the emails sent within the daily limits of the mail providers
+//! and spreads bursts of emails with a token bucket. Its state is persisted on disk,
+//! so that the limits hold across runs.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::Utc;
+use log::warn;
+
+use crate::models::{KindleError, OutgoingMail, QuotaConfig, QuotaSend, QuotaState};
+use crate::services::StateService;
+
+/// Name of the quota file in the application directory
+const QUOTA_FILE_NAME: &str = "quota.json";
+
+/// Service enforcing the sending limits
+pub struct QuotaService<'a> {
+    /// Configured limits
+    pub config: &'a QuotaConfig,
+    /// Usage of the limits, shared by the emails sent concurrently
+    state: Mutex<QuotaState>,
+}
+
+impl<'a> QuotaService<'a> {
+    /// Create a new instance of QuotaService, reading the usage saved by previous runs
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The configured limits
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Self, KindleError>` - A new QuotaService instance or an error
+    pub fn load(config: &'a QuotaConfig) -> Result<Self, KindleError> {
+        let mut state = Self::read_state()?;
+        state.prune(Utc::now().timestamp());
+
+        Ok(QuotaService {
+            config,
+            state: Mutex::new(state),
+        })
+    }
+
+    /// Count the emails that can be sent without exceeding the daily limits
+    ///
+    /// # Arguments
+    ///
+    /// * `mails` - The emails to send, in order
+    ///
+    /// # Returns
+    ///
+    /// * `usize` - The number of leading emails within the limits
+    pub fn admit(&self, mails: &[OutgoingMail]) -> usize {
+        let (mut messages, mut recipients) = self.lock().daily_usage();
+
+        for (index, mail) in mails.iter().enumerate() {
+            messages += 1;
+            recipients += mail.recipients.len();
+
+            let over_messages = self
+                .config
+                .max_messages_per_day
+                .is_some_and(|max| messages > max as usize);
+            let over_recipients = self
+                .config
+                .max_recipients_per_day
+                .is_some_and(|max| recipients > max as usize);
+            if over_messages || over_recipients {
+                return index;
+            }
+        }
+
+        mails.len()
+    }
+
+    /// Get the time at which the daily limits make room for more emails
+    ///
+    /// # Returns
+    ///
+    /// * `Option<i64>` - Timestamp at which room is made, or None if no email was sent
+    pub fn next_release(&self) -> Option<i64> {
+        self.lock().next_release()
+    }
+
+    /// Take a token from the bucket to send an email
+    ///
+    /// When the bucket is empty, the token is reserved and the caller must wait until
+    /// it is refilled.
+    ///
+    /// # Returns
+    ///
+    /// * `Duration` - The delay to wait before sending the email
+    pub fn reserve(&self) -> Duration {
+        let Some(rate) = self.config.messages_per_minute.filter(|rate| *rate > 0.0) else {
+            return Duration::ZERO;
+        };
+        let capacity = self.config.burst.max(1) as f64;
+        let now_ms = Utc::now().timestamp_millis();
+
+        let mut state = self.lock();
+        let elapsed_minutes = (now_ms - state.refilled_at_ms).max(0) as f64 / 60_000.0;
+        let tokens =
+            (state.tokens.unwrap_or(capacity) + elapsed_minutes * rate).min(capacity) - 1.0;
+        state.tokens = Some(tokens);
+        state.refilled_at_ms = now_ms;
+
+        if tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-tokens / rate * 60.0)
+        }
+    }
+
+    /// Count a sent email in the daily limits and save the usage
+    ///
+    /// A failure to save the usage is only logged, since the email was sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `mail` - The sent email
+    pub fn record(&self, mail: &OutgoingMail) {
+        let now = Utc::now().timestamp();
+
+        let mut state = self.lock();
+        state.prune(now);
+        state.sends.push(QuotaSend {
+            sent_at: now,
+            recipients: mail.recipients.len(),
+        });
+
+        if let Err(e) = Self::write_state(&state) {
+            warn!("{}", e.message);
+        }
+    }
+
+    /// Lock the usage of the limits
+    ///
+    /// # Returns
+    ///
+    /// * `MutexGuard<QuotaState>` - The usage of the limits
+    fn lock(&self) -> MutexGuard<'_, QuotaState> {
+        // The state stays consistent even if a thread panicked while holding it
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Read the usage of the limits, starting from no usage if there is none yet
+    ///
+    /// # Returns
+    ///
+    /// * `Result<QuotaState, KindleError>` - The usage of the limits or an error
+    fn read_state() -> Result<QuotaState, KindleError> {
+        StateService::read(QUOTA_FILE_NAME, "sending quota")
+    }
+
+    /// Write the usage of the limits
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The usage of the limits
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    fn write_state(state: &QuotaState) -> Result<(), KindleError> {
+        StateService::write(QUOTA_FILE_NAME, "sending quota", state)
+    }
+}
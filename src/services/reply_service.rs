@@ -0,0 +1,441 @@
+//! # Amazon Reply Service
+//!
+//! This module recognizes the emails Amazon sends back when it rejects a document or
+//! asks the sender to verify a request, and matches them to the recent sends.
+
+use std::path::Path;
+
+use log::{debug, error, warn};
+use reqwest::Url;
+
+use crate::models::{IncomingMail, SendHistory, SendStatus};
+
+/// Registrable domains of the Amazon stores, whose subdomains are also trusted
+const AMAZON_DOMAINS: [&str; 22] = [
+    "amazon.com",
+    "amazon.ca",
+    "amazon.com.mx",
+    "amazon.com.br",
+    "amazon.co.uk",
+    "amazon.de",
+    "amazon.fr",
+    "amazon.it",
+    "amazon.es",
+    "amazon.nl",
+    "amazon.se",
+    "amazon.pl",
+    "amazon.com.be",
+    "amazon.com.tr",
+    "amazon.ae",
+    "amazon.sa",
+    "amazon.eg",
+    "amazon.in",
+    "amazon.co.jp",
+    "amazon.cn",
+    "amazon.sg",
+    "amazon.com.au",
+];
+
+/// Phrases of the emails asking the sender to verify a request
+const VERIFICATION_PHRASES: [&str; 7] = [
+    "verify your request",
+    "verify the request",
+    "verify this request",
+    "verify request",
+    "approve your request",
+    "approve the request",
+    "requires verification",
+];
+
+/// Phrases of the emails reporting a document that could not be delivered
+const REJECTION_PHRASES: [&str; 5] = [
+    "could not be delivered",
+    "couldn't be delivered",
+    "could not deliver",
+    "couldn't deliver",
+    "unable to deliver",
+];
+
+/// Shortest filename without extension matched on its own, as shorter ones are likely
+/// to appear in unrelated emails
+const MIN_STEM_LENGTH: usize = 6;
+
+/// Tolerated difference between the clocks of the sender and the mail server, in seconds
+const CLOCK_SKEW_SECS: i64 = 60;
+
+/// Service for matching the replies of Amazon to the recent sends
+pub struct ReplyService {}
+
+impl ReplyService {
+    /// Update the send history with the replies of Amazon
+    ///
+    /// Each reply is matched to the sends made before it that it names a file of, or
+    /// whose Message-ID it quotes.
+    /// Sends already updated by a reply are left as they are.
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The send history
+    /// * `replies` - The emails received from Amazon
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if a send was updated, false otherwise
+    pub fn apply_replies(history: &mut SendHistory, replies: &[IncomingMail]) -> bool {
+        let mut updated = false;
+
+        for reply in replies {
+            let text = Self::normalize(&format!("{}\n{}", reply.subject, reply.body));
+            let Some(status) = Self::classify(&text) else {
+                continue;
+            };
+            debug!(
+                "Reply from {} reports {:?}: {}",
+                reply.from, status, reply.subject
+            );
+            let link = match status {
+                SendStatus::PendingVerification => Self::verification_link(&reply.body),
+                _ => None,
+            };
+
+            for record in history.records.iter_mut().filter(|record| {
+                record.status == SendStatus::Sent
+                    && record.sent_at <= reply.received_at + CLOCK_SKEW_SECS
+                    && (Self::mentions(&text, &record.filename)
+                        || record
+                            .message_id
+                            .as_ref()
+                            .is_some_and(|message_id| text.contains(&message_id.to_lowercase())))
+            }) {
+                match status {
+                    SendStatus::Rejected => error!(
+                        "Amazon could not deliver file {}: {}",
+                        record.filename, reply.subject
+                    ),
+                    _ => warn!(
+                        "Amazon asks to verify the sending of file {}, open: {}",
+                        record.filename,
+                        link.as_deref()
+                            .unwrap_or("the link in the email from Amazon")
+                    ),
+                }
+                record.status = status;
+                record.verification_link = link.clone();
+                updated = true;
+            }
+        }
+
+        updated
+    }
+
+    /// Recognize the kind of a reply of Amazon
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The normalized subject and body of the reply
+    ///
+    /// # Returns
+    ///
+    /// * `Option<SendStatus>` - The status of the send it reports, or None if it
+    ///   reports nothing
+    fn classify(text: &str) -> Option<SendStatus> {
+        // A rejection may also ask to check the document, it must not pass for a verification
+        if REJECTION_PHRASES.iter().any(|phrase| text.contains(phrase)) {
+            Some(SendStatus::Rejected)
+        } else if VERIFICATION_PHRASES
+            .iter()
+            .any(|phrase| text.contains(phrase))
+        {
+            Some(SendStatus::PendingVerification)
+        } else {
+            None
+        }
+    }
+
+    /// Check whether a reply names a file
+    ///
+    /// Amazon names documents either by their filename or by their title, which
+    /// defaults to the filename without its extension. The filename without its
+    /// extension is only matched when it is long enough to be distinctive.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The normalized subject and body of the reply
+    /// * `filename` - Name of the file
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the reply names the file, false otherwise
+    fn mentions(text: &str, filename: &str) -> bool {
+        let filename = filename.to_lowercase();
+        let stem = Path::new(&filename)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        Self::contains_word(text, &filename)
+            || (stem.chars().count() >= MIN_STEM_LENGTH && Self::contains_word(text, &stem))
+    }
+
+    /// Check whether a text contains a phrase that is not part of a longer word
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text
+    /// * `phrase` - The phrase
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the text contains the phrase, false otherwise
+    fn contains_word(text: &str, phrase: &str) -> bool {
+        if phrase.is_empty() {
+            return false;
+        }
+
+        text.match_indices(phrase).any(|(start, _)| {
+            let before = text[..start].chars().next_back();
+            let after = text[start + phrase.len()..].chars().next();
+            !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+        })
+    }
+
+    /// Find the link to follow to verify a request
+    ///
+    /// # Arguments
+    ///
+    /// * `body` - Body of the email asking for the verification
+    ///
+    /// # Returns
+    ///
+    /// * `Option<String>` - The first Amazon link about the verification, or the first
+    ///   Amazon link if none is about it
+    fn verification_link(body: &str) -> Option<String> {
+        let links: Vec<&str> = body
+            .match_indices("https://")
+            .map(|(start, _)| {
+                let rest = &body[start..];
+                let end = rest
+                    .find(|c: char| c.is_whitespace() || matches!(c, '<' | '>' | '"' | '\'' | ')'))
+                    .unwrap_or(rest.len());
+                &rest[..end]
+            })
+            .filter(|link| {
+                Url::parse(link)
+                    .ok()
+                    .and_then(|url| url.host_str().map(Self::is_amazon_domain))
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        links
+            .iter()
+            .find(|link| link.to_ascii_lowercase().contains("verif"))
+            .or(links.first())
+            .map(|link| link.to_string())
+    }
+
+    /// Check whether an email address belongs to Amazon
+    ///
+    /// # Arguments
+    ///
+    /// * `address` - The email address
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the domain of the address is an Amazon domain, false otherwise
+    pub fn is_amazon_address(address: &str) -> bool {
+        address
+            .rsplit_once('@')
+            .is_some_and(|(_, domain)| Self::is_amazon_domain(domain))
+    }
+
+    /// Check whether a host name is an Amazon domain or one of its subdomains
+    ///
+    /// # Arguments
+    ///
+    /// * `host` - The host name
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the host belongs to Amazon, false otherwise
+    fn is_amazon_domain(host: &str) -> bool {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        AMAZON_DOMAINS.iter().any(|domain| {
+            host == *domain
+                || host
+                    .strip_suffix(domain)
+                    .is_some_and(|subdomain| subdomain.ends_with('.'))
+        })
+    }
+
+    /// Normalize the text of a reply for matching
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The text in lowercase, with typographic apostrophes replaced
+    fn normalize(text: &str) -> String {
+        text.to_lowercase().replace('\u{2019}', "'")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+
+    /// Build a reply of Amazon received now
+    fn reply(subject: &str, body: &str) -> IncomingMail {
+        IncomingMail {
+            from: String::from("do-not-reply@amazon.com"),
+            subject: subject.to_string(),
+            body: body.to_string(),
+            received_at: Utc::now().timestamp(),
+        }
+    }
+
+    /// Build a history with one send of each file
+    fn history(filenames: &[&str]) -> SendHistory {
+        let mut history = SendHistory::default();
+        for (index, filename) in filenames.iter().enumerate() {
+            history.record_sent(
+                filename,
+                &[String::from("me@kindle.com")],
+                &format!("<message-{}@example.com>", index),
+                "",
+            );
+        }
+        history
+    }
+
+    #[test]
+    fn classify_recognizes_rejections_and_verifications() {
+        assert_eq!(
+            ReplyService::classify("we couldn't deliver book.epub to your kindle"),
+            Some(SendStatus::Rejected)
+        );
+        assert_eq!(
+            ReplyService::classify("please verify your request to send book.epub"),
+            Some(SendStatus::PendingVerification)
+        );
+    }
+
+    #[test]
+    fn classify_prefers_rejection_over_verification() {
+        assert_eq!(
+            ReplyService::classify(
+                "we could not deliver book.epub. please verify the request and the file format."
+            ),
+            Some(SendStatus::Rejected)
+        );
+    }
+
+    #[test]
+    fn classify_ignores_generic_errors() {
+        assert_eq!(
+            ReplyService::classify("there was a problem with your order"),
+            None
+        );
+        assert_eq!(
+            ReplyService::classify("an error occurred while processing your payment"),
+            None
+        );
+    }
+
+    #[test]
+    fn apply_replies_marks_rejected_file() {
+        let mut history = history(&["novel.epub", "other.pdf"]);
+
+        let updated = ReplyService::apply_replies(
+            &mut history,
+            &[reply(
+                "There was a problem with the document(s) you sent to Kindle",
+                "We couldn\u{2019}t deliver novel.epub to your Kindle.",
+            )],
+        );
+
+        assert!(updated);
+        assert_eq!(history.records[0].status, SendStatus::Rejected);
+        assert_eq!(history.records[1].status, SendStatus::Sent);
+    }
+
+    #[test]
+    fn apply_replies_records_verification_link() {
+        let mut history = history(&["novel.epub"]);
+
+        ReplyService::apply_replies(
+            &mut history,
+            &[reply(
+                "Verify your request",
+                "To approve novel.epub visit https://amazon.attacker.example/verify or \
+                 https://www.amazon.com/help or https://www.amazon.com/sendtokindle/verify?t=1",
+            )],
+        );
+
+        assert_eq!(history.records[0].status, SendStatus::PendingVerification);
+        assert_eq!(
+            history.records[0].verification_link.as_deref(),
+            Some("https://www.amazon.com/sendtokindle/verify?t=1")
+        );
+    }
+
+    #[test]
+    fn apply_replies_matches_long_stem_and_message_id() {
+        let mut history = history(&["my-novel.epub", "a.pdf", "b.pdf"]);
+
+        ReplyService::apply_replies(
+            &mut history,
+            &[
+                reply("Verify your request", "Approve the sending of My-Novel"),
+                reply("Verify your request", "Approve the sending of a"),
+                reply(
+                    "Your document could not be delivered",
+                    "In reply to <Message-2@example.com>",
+                ),
+            ],
+        );
+
+        assert_eq!(history.records[0].status, SendStatus::PendingVerification);
+        assert_eq!(history.records[1].status, SendStatus::Sent);
+        assert_eq!(history.records[2].status, SendStatus::Rejected);
+    }
+
+    #[test]
+    fn apply_replies_ignores_unrelated_mail() {
+        let mut history = history(&["novel.epub"]);
+
+        let updated = ReplyService::apply_replies(
+            &mut history,
+            &[
+                reply("Your order has shipped", "novel.epub is on its way"),
+                reply("Verify your request", "Approve the sending of notes.pdf"),
+            ],
+        );
+
+        assert!(!updated);
+        assert_eq!(history.records[0].status, SendStatus::Sent);
+    }
+
+    #[test]
+    fn apply_replies_ignores_replies_older_than_the_send() {
+        let mut history = history(&["novel.epub"]);
+        let mut old_reply = reply("Verify your request", "Approve novel.epub");
+        old_reply.received_at -= 60 * 60;
+
+        assert!(!ReplyService::apply_replies(&mut history, &[old_reply]));
+    }
+
+    #[test]
+    fn is_amazon_address_checks_the_domain() {
+        assert!(ReplyService::is_amazon_address("do-not-reply@amazon.com"));
+        assert!(ReplyService::is_amazon_address("kindle@eu.amazon.co.uk"));
+        assert!(!ReplyService::is_amazon_address(
+            "kindle@amazon.attacker.example"
+        ));
+        assert!(!ReplyService::is_amazon_address("kindle@notamazon.com"));
+    }
+}
@@ -0,0 +1,229 @@
+//! # Retry Service
+//!
+//! This module retries HTTP requests that fail with transient errors, using exponential
+//! backoff with jitter and honoring the `Retry-After` header.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use log::warn;
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+
+use crate::models::RetryConfig;
+
+/// Service for sending HTTP requests with a retry policy
+#[derive(Clone, Copy)]
+pub struct RetryService<'a> {
+    /// Retry policy configuration
+    pub config: &'a RetryConfig,
+}
+
+impl<'a> RetryService<'a> {
+    /// Create a new instance of RetryService
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Retry policy configuration
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new RetryService instance
+    pub fn new(config: &'a RetryConfig) -> Self {
+        RetryService { config }
+    }
+
+    /// Send a request, retrying it while it fails with a transient error
+    ///
+    /// Responses with a retryable status (408, 429, 500, 502, 503, 504) and connection
+    /// or timeout errors are retried until the maximum number of attempts is reached.
+    /// Any other response is returned as is, so callers still check the status.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - Description of the request, used in logs
+    /// * `build_request` - Closure building a new request for each attempt
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Response, reqwest::Error>` - The last response or error
+    pub async fn send<F>(
+        &self,
+        operation: &str,
+        build_request: F,
+    ) -> Result<Response, reqwest::Error>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        self.send_with_policy(operation, build_request, Self::is_retryable_status, true)
+            .await
+    }
+
+    /// Send a request that must not be repeated once the server received it
+    ///
+    /// Only throttled requests (429, 503) and connection errors, which the server did
+    /// not process, are retried. A timeout or a gateway error may come after the server
+    /// accepted the request, retrying it would then send the email again.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - Description of the request, used in logs
+    /// * `build_request` - Closure building a new request for each attempt
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Response, reqwest::Error>` - The last response or error
+    pub async fn send_non_idempotent<F>(
+        &self,
+        operation: &str,
+        build_request: F,
+    ) -> Result<Response, reqwest::Error>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        self.send_with_policy(operation, build_request, Self::is_throttling_status, false)
+            .await
+    }
+
+    /// Send a request, retrying the failures accepted by a policy
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - Description of the request, used in logs
+    /// * `build_request` - Closure building a new request for each attempt
+    /// * `is_retryable` - Whether a response status is retried
+    /// * `retry_timeouts` - Whether requests that timed out are retried
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Response, reqwest::Error>` - The last response or error
+    async fn send_with_policy<F>(
+        &self,
+        operation: &str,
+        build_request: F,
+        is_retryable: fn(StatusCode) -> bool,
+        retry_timeouts: bool,
+    ) -> Result<Response, reqwest::Error>
+    where
+        F: Fn() -> RequestBuilder,
+    {
+        let max_attempts = self.config.max_attempts.max(1);
+        let mut attempt = 1;
+
+        loop {
+            let result = build_request().send().await;
+
+            let (reason, retry_after) = match &result {
+                Ok(response) if is_retryable(response.status()) => {
+                    (response.status().to_string(), Self::retry_after(response))
+                }
+                Err(e) if e.is_connect() || (retry_timeouts && e.is_timeout()) => {
+                    (e.to_string(), None)
+                }
+                _ => return result,
+            };
+
+            if attempt >= max_attempts {
+                return result;
+            }
+
+            let delay = retry_after
+                .unwrap_or_else(|| self.backoff_delay(attempt))
+                .min(Duration::from_secs(self.config.max_delay_secs));
+
+            warn!(
+                "{} failed (attempt {}/{}): {}, retrying in {:.1}s",
+                operation,
+                attempt,
+                max_attempts,
+                reason,
+                delay.as_secs_f64()
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Check whether a response status is worth retrying
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The response status
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true for throttling and transient server errors
+    fn is_retryable_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::REQUEST_TIMEOUT
+                | StatusCode::TOO_MANY_REQUESTS
+                | StatusCode::INTERNAL_SERVER_ERROR
+                | StatusCode::BAD_GATEWAY
+                | StatusCode::SERVICE_UNAVAILABLE
+                | StatusCode::GATEWAY_TIMEOUT
+        )
+    }
+
+    /// Check whether a response status reports a request refused without being processed
+    ///
+    /// # Arguments
+    ///
+    /// * `status` - The response status
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true for throttling responses
+    fn is_throttling_status(status: StatusCode) -> bool {
+        matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE
+        )
+    }
+
+    /// Read the delay requested by the `Retry-After` header
+    ///
+    /// # Arguments
+    ///
+    /// * `response` - The response to inspect
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Duration>` - The requested delay, if the header is present and valid
+    fn retry_after(response: &Response) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+        // The header is either a number of seconds or an HTTP date
+        if let Ok(seconds) = value.parse::<u64>() {
+            return Some(Duration::from_secs(seconds));
+        }
+
+        let date = DateTime::parse_from_rfc2822(value).ok()?;
+        let seconds = (date.with_timezone(&Utc) - Utc::now()).num_seconds();
+        Some(Duration::from_secs(seconds.max(0) as u64))
+    }
+
+    /// Compute the exponential backoff delay of an attempt
+    ///
+    /// The delay doubles after each attempt and a random jitter keeps it between
+    /// half and all of the exponential value.
+    ///
+    /// # Arguments
+    ///
+    /// * `attempt` - Number of the failed attempt, starting at 1
+    ///
+    /// # Returns
+    ///
+    /// * `Duration` - Delay before the next attempt
+    fn backoff_delay(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .config
+            .initial_delay_ms
+            .saturating_mul(1u64 << (attempt - 1).min(20));
+        let capped = exponential.min(self.config.max_delay_secs.saturating_mul(1000));
+        let jittered = rand::rng().random_range(capped / 2..=capped);
+        Duration::from_millis(jittered)
+    }
+}
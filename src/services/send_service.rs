@@ -1,20 +1,39 @@
 //! # Send Service Module
 //!
 //! This module orchestrates the sending of e-book files to Kindle devices
-//! by coordinating between Azure authentication and Kindle email services.
+//! by coordinating between the Kindle email service and a mail transport.
 
+use chrono::{DateTime, Local, Utc};
+use futures_util::{Stream, StreamExt, stream};
 use log::{info, warn};
+use std::collections::BTreeSet;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
-use crate::models::{Config, KindleError};
-use crate::services::{AzureService, FileService, KindleService};
+use crate::models::{
+    Config, DeliveryMode, KindleError, MailAttachment, OutgoingMail, SendHistory, SendStatus,
+};
+use crate::services::{
+    DeliveryService, FileService, HistoryService, KindleService, MailTransport, QuotaService,
+    ReplyService,
+};
 
-/// Service that coordinates the Azure authentication and Kindle email services
+/// Largest number of attachments accepted by the Send-to-Kindle service in one email
+const MAX_ATTACHMENTS_PER_EMAIL: usize = 25;
+
+/// Largest total size of the attachments accepted by the Send-to-Kindle service in one
+/// email, in megabytes
+const MAX_EMAIL_SIZE_MB: u64 = 50;
+
+/// Age of the oldest sends kept in the send history, in seconds (30 days)
+const HISTORY_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+
+/// Service that coordinates the Kindle email service and a mail transport
 /// to send e-book files to Kindle devices
-pub struct SendService<'a> {
-    /// Azure service for authentication and API access
-    pub azure_service: AzureService<'a>,
-    /// Kindle service for sending e-books via email
+pub struct SendService<'a, T: MailTransport> {
+    /// Mail transport delivering the emails
+    pub transport: T,
+    /// Kindle service for composing the emails
     pub kindle_service: KindleService<'a>,
     /// File service for file system operations
     pub file_service: FileService,
@@ -22,25 +41,21 @@ pub struct SendService<'a> {
     pub config: &'a Config,
 }
 
-impl<'a> SendService<'a> {
+impl<'a, T: MailTransport> SendService<'a, T> {
     /// Create a new instance of SendService
     ///
     /// # Arguments
     ///
-    /// * `azure_service` - The Azure service for authentication
-    /// * `kindle_service` - The Kindle service for sending emails
+    /// * `transport` - The mail transport delivering the emails
+    /// * `kindle_service` - The Kindle service for composing emails
     /// * `config` - Configuration for the service
     ///
     /// # Returns
     ///
     /// * `Self` - A new SendService instance
-    pub fn new(
-        azure_service: AzureService<'a>,
-        kindle_service: KindleService<'a>,
-        config: &'a Config,
-    ) -> Self {
+    pub fn new(transport: T, kindle_service: KindleService<'a>, config: &'a Config) -> Self {
         SendService {
-            azure_service,
+            transport,
             kindle_service,
             file_service: FileService::new(),
             config,
@@ -49,13 +64,13 @@ impl<'a> SendService<'a> {
 
     /// Send e-book files to Kindle devices
     ///
-    /// Authenticates with Azure and sends all configured e-book files
+    /// Prepares the mail transport and sends all configured e-book files
     /// to the Kindle email addresses, then moves them to the sent directory.
     ///
     /// # Returns
     ///
     /// * `Result<(), KindleError>` - Success or an error
-    pub async fn send_files(&self) -> Result<(), KindleError> {
+    pub async fn send_files(&mut self) -> Result<(), KindleError> {
         info!("Starting file sending process...");
 
         // List all files in the to-send directory
@@ -70,56 +85,89 @@ impl<'a> SendService<'a> {
 
         info!("Found {} files to send", files.len());
 
-        // Authenticate with Azure
-        let access_token = self.azure_service.authenticate().await?;
+        // Prepare the transport (authentication, connection checks)
+        self.transport.prepare().await?;
 
         // Send each file and move it to the sent directory
         let mut success_count = 0;
         let mut failure_count = 0;
 
+        // Describe the files as attachments, files that cannot be attached are failed
+        let mut attachments = Vec::new();
         for file_path in &files {
-            let path = Path::new(&file_path);
-            let filename = path
-                .file_name()
-                .map(|name| name.to_string_lossy().to_string())
-                .unwrap_or_else(|| String::from("Unknown file"));
-
-            info!("Sending file: {}", filename);
-
-            match self
-                .kindle_service
-                .send_file(access_token.clone(), file_path)
-                .await
-            {
-                Ok(_) => {
-                    info!("Successfully sent file: {}", filename);
-
-                    // Move file to sent directory
-                    match self
-                        .file_service
-                        .move_file(file_path, &self.config.ebook_sent_directory)
-                    {
-                        Ok(_) => {
-                            info!("Moved file to sent directory: {}", filename);
-                            success_count += 1;
-                        }
-                        Err(e) => {
-                            warn!("Failed to move file {}: {}", filename, e.message);
-                            failure_count += 1;
-                        }
-                    }
-                }
+            match self.kindle_service.build_attachment(file_path) {
+                Ok(attachment) => attachments.push(attachment),
                 Err(e) => {
-                    warn!("Failed to send file {}: {}", filename, e.message);
+                    warn!(
+                        "Failed to send file {}: {}",
+                        Self::filename(file_path),
+                        e.message
+                    );
                     failure_count += 1;
                 }
             }
         }
 
+        let quota = QuotaService::load(&self.config.quota)?;
+        let mut history = SendHistory::default();
+        let started_at = Utc::now().timestamp();
+
+        let (sent_count, failed_count, deferred_count) = match self.config.delivery {
+            DeliveryMode::Combined => self.send_combined(attachments, &quota, &mut history).await,
+            DeliveryMode::PerRecipient => {
+                self.send_per_recipient(attachments, &quota, &mut history)
+                    .await?
+            }
+        };
+        success_count += sent_count;
+        failure_count += failed_count;
+
+        // The sends of this run are added to the saved history, a history that cannot
+        // be read is left as it is
+        let mut save_history = false;
+        if !history.records.is_empty() {
+            match HistoryService::read_history() {
+                Ok(mut saved) => {
+                    saved.prune(Utc::now().timestamp() - HISTORY_RETENTION_SECS);
+                    saved.records.append(&mut history.records);
+                    history = saved;
+                    save_history = true;
+                    Self::save_history(&history);
+                }
+                Err(e) => warn!("{}, the sends of this run are not recorded", e.message),
+            }
+        }
+
+        // Files rejected by Amazon were sent, but did not reach the devices
+        let mut pending_count = 0;
+        if self.config.reply_check.enabled && sent_count > 0 {
+            let (rejected, pending) = self
+                .check_replies(&mut history, started_at, save_history)
+                .await;
+            for filename in &rejected {
+                self.move_rejected_file(filename);
+            }
+            success_count = success_count.saturating_sub(rejected.len() + pending);
+            failure_count += rejected.len();
+            pending_count = pending;
+        }
+
         info!(
             "Sending process completed. Successfully sent: {}, Failed: {}",
             success_count, failure_count
         );
+        if pending_count > 0 {
+            warn!(
+                "{} files wait for the verification of the request before delivery",
+                pending_count
+            );
+        }
+        if deferred_count > 0 {
+            warn!(
+                "{} files were deferred to the next run by the sending quota",
+                deferred_count
+            );
+        }
 
         if failure_count > 0 {
             return Err(KindleError {
@@ -129,4 +177,661 @@ impl<'a> SendService<'a> {
 
         Ok(())
     }
+
+    /// Send the files in emails addressed to every receiver at once
+    ///
+    /// A file is sent when the email carrying it is accepted by the transport.
+    ///
+    /// # Arguments
+    ///
+    /// * `attachments` - The files to send
+    /// * `quota` - The sending limits
+    /// * `history` - The send history the sent files are added to
+    ///
+    /// # Returns
+    ///
+    /// * `(usize, usize, usize)` - The number of files sent, failed and deferred
+    async fn send_combined(
+        &self,
+        attachments: Vec<MailAttachment>,
+        quota: &QuotaService<'_>,
+        history: &mut SendHistory,
+    ) -> (usize, usize, usize) {
+        let mails: Vec<OutgoingMail> = self
+            .plan_batches(attachments)
+            .into_iter()
+            .map(|batch| self.kindle_service.compose_mail(batch))
+            .collect();
+        let (mails, deferred) = self.apply_quota(quota, mails);
+        let deferred_count = deferred.iter().map(|mail| mail.attachments.len()).sum();
+
+        let mut success_count = 0;
+        let mut failure_count = 0;
+
+        let mut results = self.send_mails(&mails, quota);
+        while let Some((mail, result)) = results.next().await {
+            match result {
+                Ok(_) => {
+                    for attachment in &mail.attachments {
+                        info!("Successfully sent file: {}", attachment.name);
+                        history.record_sent(
+                            &attachment.name,
+                            &mail.recipients,
+                            &mail.message_id,
+                            &attachment.sha256,
+                        );
+
+                        if self.move_sent_file(attachment) {
+                            success_count += 1;
+                        } else {
+                            failure_count += 1;
+                        }
+                    }
+                }
+                Err(e) => {
+                    for attachment in &mail.attachments {
+                        warn!("Failed to send file {}: {}", attachment.name, e.message);
+                        failure_count += 1;
+                    }
+                }
+            }
+        }
+
+        (success_count, failure_count, deferred_count)
+    }
+
+    /// Send the files in one email per receiver, recording the outcome for each of them
+    ///
+    /// Files already delivered to a receiver by a previous run are not sent to it
+    /// again. A file is sent once every receiver got it, its records are then dropped.
+    /// A file whose email to a receiver is deferred by the sending quota is kept for
+    /// the next run.
+    ///
+    /// # Arguments
+    ///
+    /// * `attachments` - The files to send
+    /// * `quota` - The sending limits
+    /// * `history` - The send history the sent files are added to
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(usize, usize, usize), KindleError>` - The number of files sent, failed and
+    ///   deferred, or an error
+    async fn send_per_recipient(
+        &self,
+        attachments: Vec<MailAttachment>,
+        quota: &QuotaService<'_>,
+        history: &mut SendHistory,
+    ) -> Result<(usize, usize, usize), KindleError> {
+        let receivers = self.kindle_service.emails;
+        let mut deliveries = DeliveryService::read_log()?;
+
+        let mut mails = Vec::new();
+        for receiver in receivers {
+            let pending: Vec<MailAttachment> = attachments
+                .iter()
+                .filter(|attachment| !deliveries.is_delivered(&attachment.name, receiver))
+                .cloned()
+                .collect();
+
+            let skipped = attachments.len() - pending.len();
+            if skipped > 0 {
+                info!(
+                    "Skipping {} files already delivered to {}",
+                    skipped, receiver
+                );
+            }
+
+            for batch in self.plan_batches(pending) {
+                mails.push(self.kindle_service.compose_mail_to(receiver, batch));
+            }
+        }
+
+        let (mails, deferred) = self.apply_quota(quota, mails);
+        let deferred_files: BTreeSet<&str> = deferred
+            .iter()
+            .flat_map(|mail| &mail.attachments)
+            .map(|attachment| attachment.name.as_str())
+            .collect();
+
+        let mut results = self.send_mails(&mails, quota);
+        while let Some((mail, result)) = results.next().await {
+            let receiver = &mail.recipients[0];
+            for attachment in &mail.attachments {
+                match &result {
+                    Ok(_) => {
+                        info!("Successfully sent file {} to {}", attachment.name, receiver);
+                        history.record_sent(
+                            &attachment.name,
+                            &mail.recipients,
+                            &mail.message_id,
+                            &attachment.sha256,
+                        );
+                    }
+                    Err(e) => warn!(
+                        "Failed to send file {} to {}: {}",
+                        attachment.name, receiver, e.message
+                    ),
+                }
+                deliveries.record(&attachment.name, receiver, result.as_ref().err());
+            }
+
+            // Save the records after each email, so that an interrupted run does not
+            // send duplicates when it is restarted
+            if let Err(e) = DeliveryService::write_log(&deliveries) {
+                warn!("{}", e.message);
+            }
+        }
+
+        let mut success_count = 0;
+        let mut failure_count = 0;
+        let mut deferred_count = 0;
+
+        for attachment in &attachments {
+            let missing: Vec<&str> = receivers
+                .iter()
+                .filter(|receiver| !deliveries.is_delivered(&attachment.name, receiver))
+                .map(|receiver| receiver.as_str())
+                .collect();
+
+            if !missing.is_empty() && deferred_files.contains(attachment.name.as_str()) {
+                deferred_count += 1;
+            } else if !missing.is_empty() {
+                warn!(
+                    "File {} was not delivered to: {}",
+                    attachment.name,
+                    missing.join(", ")
+                );
+                failure_count += 1;
+            } else if self.move_sent_file(attachment) {
+                deliveries.remove_file(&attachment.name);
+                success_count += 1;
+            } else {
+                failure_count += 1;
+            }
+        }
+
+        DeliveryService::write_log(&deliveries)?;
+
+        Ok((success_count, failure_count, deferred_count))
+    }
+
+    /// Keep the emails within the daily limits, deferring the others to the next run
+    ///
+    /// # Arguments
+    ///
+    /// * `quota` - The sending limits
+    /// * `mails` - The emails to send, in order
+    ///
+    /// # Returns
+    ///
+    /// * `(Vec<OutgoingMail>, Vec<OutgoingMail>)` - The emails to send and the deferred ones
+    fn apply_quota(
+        &self,
+        quota: &QuotaService<'_>,
+        mut mails: Vec<OutgoingMail>,
+    ) -> (Vec<OutgoingMail>, Vec<OutgoingMail>) {
+        let deferred = mails.split_off(quota.admit(&mails));
+        if deferred.is_empty() {
+            return (mails, deferred);
+        }
+
+        let release = quota
+            .next_release()
+            .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+            .map(|date| {
+                format!(
+                    ", more can be sent after {}",
+                    date.with_timezone(&Local).format("%Y-%m-%d %H:%M")
+                )
+            })
+            .unwrap_or_default();
+        warn!(
+            "Daily sending quota reached, deferring {} of {} emails to the next run{}",
+            deferred.len(),
+            mails.len() + deferred.len(),
+            release
+        );
+        for mail in &deferred {
+            for attachment in &mail.attachments {
+                info!(
+                    "Deferred file {} to {}",
+                    attachment.name,
+                    mail.recipients.join(", ")
+                );
+            }
+        }
+
+        (mails, deferred)
+    }
+
+    /// Wait for the replies of Amazon to the files sent by this run
+    ///
+    /// The inbox is checked until the configured wait is over. Replies also update
+    /// the sends of the previous runs, the send history is saved after each check
+    /// that changed it.
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The send history
+    /// * `started_at` - Timestamp of the start of the sending
+    /// * `save_history` - Whether the send history is saved after the checks
+    ///
+    /// # Returns
+    ///
+    /// * `(BTreeSet<String>, usize)` - The names of the files sent by this run that
+    ///   Amazon rejected, and the number of files waiting for a verification
+    async fn check_replies(
+        &self,
+        history: &mut SendHistory,
+        started_at: i64,
+        save_history: bool,
+    ) -> (BTreeSet<String>, usize) {
+        let reply_check = &self.config.reply_check;
+        let since = Utc::now().timestamp() - reply_check.lookback_hours as i64 * 60 * 60;
+        let interval = Duration::from_secs(reply_check.poll_interval_secs.max(1));
+        let deadline = Instant::now() + Duration::from_secs(reply_check.wait_secs);
+
+        info!(
+            "Waiting up to {} seconds for replies from Amazon...",
+            reply_check.wait_secs
+        );
+        loop {
+            match self.transport.fetch_amazon_replies(since).await {
+                Ok(replies) => {
+                    if ReplyService::apply_replies(history, &replies) && save_history {
+                        Self::save_history(history);
+                    }
+                }
+                Err(e) => {
+                    warn!("Failed to check the replies from Amazon: {}", e.message);
+                    break;
+                }
+            }
+
+            if Instant::now() + interval > deadline {
+                break;
+            }
+            tokio::time::sleep(interval).await;
+        }
+
+        let sent_files = |status: SendStatus| -> BTreeSet<String> {
+            history
+                .records
+                .iter()
+                .filter(|record| record.sent_at >= started_at && record.status == status)
+                .map(|record| record.filename.clone())
+                .collect()
+        };
+        let rejected = sent_files(SendStatus::Rejected);
+        let pending = sent_files(SendStatus::PendingVerification)
+            .difference(&rejected)
+            .count();
+        (rejected, pending)
+    }
+
+    /// Send emails with the transport
+    ///
+    /// Up to `jobs` emails are sent at once, at the rate allowed by the sending quota.
+    /// Results are yielded in the order of the emails, so that the outcome of each
+    /// file is logged in a stable order. When emails are sent concurrently, the start
+    /// of an email is logged when its result is yielded, next to its outcome.
+    ///
+    /// # Arguments
+    ///
+    /// * `mails` - The emails to send
+    /// * `quota` - The sending limits
+    ///
+    /// # Returns
+    ///
+    /// * `impl Stream` - Each email with the result of its sending
+    fn send_mails<'m>(
+        &'m self,
+        mails: &'m [OutgoingMail],
+        quota: &'m QuotaService<'_>,
+    ) -> impl Stream<Item = (&'m OutgoingMail, Result<(), KindleError>)> + 'm {
+        let jobs = self.config.jobs.max(1);
+        let mail_count = mails.len();
+        let log_on_start = jobs == 1;
+
+        stream::iter(mails.iter().enumerate())
+            .map(move |(index, mail)| async move {
+                let delay = quota.reserve();
+                if !delay.is_zero() {
+                    info!(
+                        "Sending rate limit reached, waiting {:.1} seconds",
+                        delay.as_secs_f64()
+                    );
+                    tokio::time::sleep(delay).await;
+                }
+
+                if log_on_start {
+                    self.log_sending(index + 1, mail_count, mail);
+                }
+                let result = self.transport.send(mail).await;
+                if result.is_ok() {
+                    quota.record(mail);
+                }
+                (index, mail, result)
+            })
+            .buffered(jobs)
+            .map(move |(index, mail, result)| {
+                if !log_on_start {
+                    self.log_sending(index + 1, mail_count, mail);
+                }
+                (mail, result)
+            })
+    }
+
+    /// Log the start of the sending of an email
+    ///
+    /// # Arguments
+    ///
+    /// * `position` - Position of the email, starting at 1
+    /// * `total` - Number of emails to send
+    /// * `mail` - The email being sent
+    fn log_sending(&self, position: usize, total: usize, mail: &OutgoingMail) {
+        let filenames = mail
+            .attachments
+            .iter()
+            .map(|attachment| attachment.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        // Name the receiver when each of them gets their own email
+        let receiver = match self.config.delivery {
+            DeliveryMode::Combined => String::new(),
+            DeliveryMode::PerRecipient => format!(" to {}", mail.recipients.join(", ")),
+        };
+
+        if mail.attachments.len() == 1 {
+            info!(
+                "[{}/{}] Sending file{}: {}",
+                position, total, receiver, filenames
+            );
+        } else {
+            info!(
+                "[{}/{}] Sending {} files in one email{}: {}",
+                position,
+                total,
+                mail.attachments.len(),
+                receiver,
+                filenames
+            );
+        }
+    }
+
+    /// Group attachments into the emails that carry them
+    ///
+    /// Without batching, each attachment is sent in its own email. With batching,
+    /// attachments are packed first-fit into emails that respect the configured
+    /// total size and number of attachments. An attachment larger than the size
+    /// budget is sent alone. Attachments whose extension has its own message templates
+    /// are only bundled together, so that the templates still apply.
+    ///
+    /// # Arguments
+    ///
+    /// * `attachments` - The attachments to send
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Vec<MailAttachment>>` - The attachments of each email
+    fn plan_batches(&self, attachments: Vec<MailAttachment>) -> Vec<Vec<MailAttachment>> {
+        let batch_config = &self.config.batch;
+        if !batch_config.enabled {
+            return attachments
+                .into_iter()
+                .map(|attachment| vec![attachment])
+                .collect();
+        }
+
+        let max_size = batch_config.max_size_mb.clamp(1, MAX_EMAIL_SIZE_MB) * 1024 * 1024;
+        let max_attachments = batch_config
+            .max_attachments
+            .clamp(1, MAX_ATTACHMENTS_PER_EMAIL);
+
+        let message = &self.config.message;
+        let mut batches: Vec<Vec<MailAttachment>> = Vec::new();
+        for attachment in attachments {
+            let key = message.override_key(&attachment.name);
+            let batch = batches.iter_mut().find(|batch| {
+                let size: u64 = batch.iter().map(|attachment| attachment.size).sum();
+                batch.len() < max_attachments
+                    && size + attachment.size <= max_size
+                    && message.override_key(&batch[0].name) == key
+            });
+
+            match batch {
+                Some(batch) => batch.push(attachment),
+                None => batches.push(vec![attachment]),
+            }
+        }
+
+        batches
+    }
+
+    /// Move a sent file to the sent directory
+    ///
+    /// # Arguments
+    ///
+    /// * `attachment` - The attachment of the sent file
+    ///
+    /// # Returns
+    ///
+    /// * `bool` - true if the file was moved, false otherwise
+    fn move_sent_file(&self, attachment: &MailAttachment) -> bool {
+        match self
+            .file_service
+            .move_file(&attachment.path, &self.config.ebook_sent_directory)
+        {
+            Ok(_) => {
+                info!("Moved file to sent directory: {}", attachment.name);
+                true
+            }
+            Err(e) => {
+                warn!("Failed to move file {}: {}", attachment.name, e.message);
+                false
+            }
+        }
+    }
+
+    /// Move a file rejected by Amazon out of the sent directory
+    ///
+    /// The file is moved to the rejected directory, so that it is not taken for
+    /// delivered, nor sent again as is by the next run.
+    ///
+    /// # Arguments
+    ///
+    /// * `filename` - Name of the rejected file
+    fn move_rejected_file(&self, filename: &str) {
+        let rejected_directory = match &self.config.reply_check.rejected_directory {
+            Some(directory) => Path::new(directory).to_path_buf(),
+            None => Path::new(&self.config.ebook_sent_directory).join("rejected"),
+        };
+        let sent_path = Path::new(&self.config.ebook_sent_directory).join(filename);
+
+        match self.file_service.move_file(&sent_path, &rejected_directory) {
+            Ok(_) => info!(
+                "Moved rejected file to {}: {}",
+                rejected_directory.display(),
+                filename
+            ),
+            Err(e) => warn!("Failed to move rejected file {}: {}", filename, e.message),
+        }
+    }
+
+    /// Save the send history, a failure only loses the tracking of the sends
+    ///
+    /// # Arguments
+    ///
+    /// * `history` - The send history
+    fn save_history(history: &SendHistory) {
+        if let Err(e) = HistoryService::write_history(history) {
+            warn!("{}", e.message);
+        }
+    }
+
+    /// Get the filename of a file path for logging purposes
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The filename
+    fn filename(file_path: &str) -> String {
+        Path::new(file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| String::from("Unknown file"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    /// Transport that sends nothing, batches are planned without it
+    struct NoTransport;
+
+    impl MailTransport for NoTransport {
+        async fn prepare(&mut self) -> Result<(), KindleError> {
+            Ok(())
+        }
+
+        async fn send(&self, _mail: &OutgoingMail) -> Result<(), KindleError> {
+            Ok(())
+        }
+    }
+
+    fn config(extra: serde_json::Value) -> Config {
+        let mut value = json!({
+            "ebook_to_send_directory": "in",
+            "ebook_sent_directory": "sent",
+            "receivers": ["kindle@kindle.com"],
+        });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn attachment(name: &str, size: u64) -> MailAttachment {
+        MailAttachment {
+            path: name.to_string(),
+            name: name.to_string(),
+            content_type: "application/octet-stream".to_string(),
+            format: None,
+            size,
+            sha256: String::new(),
+        }
+    }
+
+    /// Plan the batches of attachments and return the names of each batch
+    fn plan(config: &Config, attachments: Vec<MailAttachment>) -> Vec<Vec<String>> {
+        let service = SendService::new(
+            NoTransport,
+            KindleService::new(&config.receivers, &config.message),
+            config,
+        );
+        service
+            .plan_batches(attachments)
+            .into_iter()
+            .map(|batch| {
+                batch
+                    .into_iter()
+                    .map(|attachment| attachment.name)
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plan_batches_sends_each_file_alone_without_batching() {
+        let config = config(json!({}));
+        let batches = plan(
+            &config,
+            vec![attachment("a.epub", 1), attachment("b.epub", 1)],
+        );
+
+        assert_eq!(batches, vec![vec!["a.epub"], vec!["b.epub"]]);
+    }
+
+    #[test]
+    fn plan_batches_packs_first_fit() {
+        let config = config(json!({ "batch": { "enabled": true, "max_size_mb": 10 } }));
+        let batches = plan(
+            &config,
+            vec![
+                attachment("a.epub", 6 * MB),
+                attachment("b.epub", 5 * MB),
+                attachment("c.epub", 4 * MB),
+                attachment("d.epub", 3 * MB),
+                attachment("e.epub", 12 * MB),
+            ],
+        );
+
+        assert_eq!(
+            batches,
+            vec![
+                vec!["a.epub", "c.epub"],
+                vec!["b.epub", "d.epub"],
+                vec!["e.epub"],
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_batches_caps_attachments_per_email() {
+        let config = config(json!({ "batch": { "enabled": true, "max_attachments": 100 } }));
+        let attachments = (0..30)
+            .map(|index| attachment(&format!("{}.epub", index), 1))
+            .collect();
+        let sizes: Vec<usize> = plan(&config, attachments).iter().map(Vec::len).collect();
+
+        assert_eq!(sizes, vec![MAX_ATTACHMENTS_PER_EMAIL, 5]);
+    }
+
+    #[test]
+    fn plan_batches_caps_size_per_email() {
+        let config = config(json!({ "batch": { "enabled": true, "max_size_mb": 500 } }));
+        let batches = plan(
+            &config,
+            vec![
+                attachment("a.epub", 30 * MB),
+                attachment("b.epub", 30 * MB),
+                attachment("c.epub", 20 * MB),
+            ],
+        );
+
+        assert_eq!(batches, vec![vec!["a.epub", "c.epub"], vec!["b.epub"]]);
+    }
+
+    #[test]
+    fn plan_batches_keeps_overridden_extensions_apart() {
+        let config = config(json!({
+            "batch": { "enabled": true },
+            "message": { "overrides": { "pdf": { "subject": "convert" } } },
+        }));
+        let batches = plan(
+            &config,
+            vec![
+                attachment("a.pdf", 1),
+                attachment("b.epub", 1),
+                attachment("c.PDF", 1),
+                attachment("d.txt", 1),
+            ],
+        );
+
+        assert_eq!(
+            batches,
+            vec![vec!["a.pdf", "c.PDF"], vec!["b.epub", "d.txt"]]
+        );
+    }
 }
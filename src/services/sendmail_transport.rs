@@ -0,0 +1,206 @@
+//! # Sendmail Transport
+//!
+//! This module delivers emails by piping them into a sendmail-compatible command
+//! (`sendmail`, `msmtp`, `nullmailer`...), reusing the relay configured on the machine.
+
+use std::path::Path;
+use std::process::Stdio;
+
+use log::info;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::models::{KindleError, OutgoingMail, SendmailConfig};
+use crate::services::{MailTransport, MimeService};
+
+/// Mail transport sending emails through a local sendmail-compatible command
+pub struct SendmailTransport<'a> {
+    /// Sendmail command configuration
+    pub config: &'a SendmailConfig,
+}
+
+impl<'a> SendmailTransport<'a> {
+    /// Create a new instance of SendmailTransport
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Sendmail command configuration
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new SendmailTransport instance
+    pub fn new(config: &'a SendmailConfig) -> Self {
+        SendmailTransport { config }
+    }
+}
+
+impl MailTransport for SendmailTransport<'_> {
+    async fn prepare(&mut self) -> Result<(), KindleError> {
+        // Commands given without a path are resolved through PATH when spawned
+        let command = Path::new(&self.config.command);
+        if command.is_absolute() && !command.exists() {
+            return Err(KindleError {
+                message: format!("Sendmail command not found: {}", self.config.command),
+            });
+        }
+        Ok(())
+    }
+
+    async fn send(&self, mail: &OutgoingMail) -> Result<(), KindleError> {
+        let message = MimeService::build_message(mail, &self.config.from)?.formatted();
+
+        // The sendmail interface expects local line endings
+        let message = String::from_utf8_lossy(&message).replace("\r\n", "\n");
+
+        let mut child = Command::new(&self.config.command)
+            .args(&self.config.args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| KindleError {
+                message: format!(
+                    "Failed to run sendmail command {}: {}",
+                    self.config.command, e
+                ),
+            })?;
+
+        // Write the message while reading the output, so that a command filling its
+        // stderr pipe cannot block the write, then close stdin to end the input
+        let stdin = child.stdin.take();
+        let write = async move {
+            match stdin {
+                Some(mut stdin) => stdin.write_all(message.as_bytes()).await,
+                None => Ok(()),
+            }
+        };
+        let (write_result, output) = tokio::join!(write, child.wait_with_output());
+
+        let output = output.map_err(|e| KindleError {
+            message: format!(
+                "Failed to wait for sendmail command {}: {}",
+                self.config.command, e
+            ),
+        })?;
+
+        if !output.status.success() {
+            return Err(KindleError {
+                message: format!(
+                    "Sendmail command {} failed ({}): {}",
+                    self.config.command,
+                    output.status,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            });
+        }
+
+        write_result.map_err(|e| KindleError {
+            message: format!(
+                "Failed to write message to sendmail command {}: {}",
+                self.config.command, e
+            ),
+        })?;
+
+        info!("Email with attachment sent successfully!");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(script: &str) -> SendmailConfig {
+        SendmailConfig {
+            command: String::from("sh"),
+            args: vec![String::from("-c"), script.to_string()],
+            from: String::from("sender@example.com"),
+        }
+    }
+
+    fn mail(path: &str) -> OutgoingMail {
+        OutgoingMail {
+            recipients: vec![String::from("me@kindle.com")],
+            subject: String::from("Books"),
+            body: String::from("Sent by kindle-sender"),
+            attachments: vec![crate::models::MailAttachment {
+                path: path.to_string(),
+                name: String::from("book.epub"),
+                content_type: String::from("application/epub+zip"),
+                format: None,
+                size: 0,
+                sha256: String::new(),
+            }],
+            message_id: String::from("<run-1@kindle-sender.local>"),
+            headers: Vec::new(),
+        }
+    }
+
+    /// Write an attachment larger than a pipe buffer
+    fn write_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "kindle-sender-sendmail-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, vec![b'x'; 512 * 1024]).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn send_pipes_message_to_command() {
+        let output = std::env::temp_dir().join(format!(
+            "kindle-sender-sendmail-{}-message.eml",
+            std::process::id()
+        ));
+        let config = config(&format!("cat > '{}'", output.display()));
+        let path = write_file("piped.epub");
+
+        SendmailTransport::new(&config)
+            .send(&mail(&path))
+            .await
+            .unwrap();
+        let message = std::fs::read_to_string(&output).unwrap();
+        std::fs::remove_file(path).unwrap();
+        std::fs::remove_file(output).unwrap();
+
+        assert!(message.contains("To: me@kindle.com\n"));
+        assert!(message.contains("Message-ID: <run-1@kindle-sender.local>\n"));
+        assert!(!message.contains('\r'));
+    }
+
+    #[tokio::test]
+    async fn send_reports_exit_status_and_stderr() {
+        let config = config("cat >/dev/null; echo err >&2; exit 3");
+        let path = write_file("failed.epub");
+
+        let error = SendmailTransport::new(&config)
+            .send(&mail(&path))
+            .await
+            .unwrap_err();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(
+            error.message.contains("exit status: 3"),
+            "{}",
+            error.message
+        );
+        assert!(error.message.ends_with(": err"), "{}", error.message);
+    }
+
+    #[tokio::test]
+    async fn send_does_not_block_on_full_stderr() {
+        // The command fills its stderr pipe before reading the message
+        let config = config("head -c 200000 /dev/zero >&2; cat >/dev/null");
+        let path = write_file("stderr.epub");
+
+        let result = tokio::time::timeout(
+            std::time::Duration::from_secs(10),
+            SendmailTransport::new(&config).send(&mail(&path)),
+        )
+        .await;
+        std::fs::remove_file(path).unwrap();
+
+        assert!(result.expect("sendmail command blocked").is_ok());
+    }
+}
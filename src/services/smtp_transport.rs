@@ -0,0 +1,268 @@
+//! # SMTP Transport
+//!
+//! This module delivers emails through an SMTP server, using STARTTLS or implicit TLS
+//! and AUTH PLAIN/LOGIN authentication.
+
+use lettre::transport::smtp::authentication::{Credentials, Mechanism};
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use log::info;
+
+use crate::models::{KindleError, OutgoingMail, SmtpAuthMechanism, SmtpConfig, SmtpSecurity};
+use crate::services::{MailTransport, MimeService};
+
+/// Mail transport sending emails through an SMTP server
+pub struct SmtpTransport<'a> {
+    /// SMTP server configuration
+    pub config: &'a SmtpConfig,
+    /// SMTP client created while preparing the transport
+    mailer: Option<AsyncSmtpTransport<Tokio1Executor>>,
+}
+
+impl<'a> SmtpTransport<'a> {
+    /// Create a new instance of SmtpTransport
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - SMTP server configuration
+    ///
+    /// # Returns
+    ///
+    /// * `Self` - A new SmtpTransport instance
+    pub fn new(config: &'a SmtpConfig) -> Self {
+        SmtpTransport {
+            config,
+            mailer: None,
+        }
+    }
+
+    /// Build the SMTP client from the configuration
+    ///
+    /// # Returns
+    ///
+    /// * `Result<AsyncSmtpTransport<Tokio1Executor>, KindleError>` - The SMTP client or an error
+    fn build_mailer(&self) -> Result<AsyncSmtpTransport<Tokio1Executor>, KindleError> {
+        let host = self.config.host.as_str();
+        let mut builder = match self.config.security {
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .map_err(|e| KindleError {
+                    message: format!("Failed to configure STARTTLS for {}: {}", host, e),
+                })?,
+            SmtpSecurity::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(host).map_err(|e| KindleError {
+                    message: format!("Failed to configure TLS for {}: {}", host, e),
+                })?
+            }
+        };
+
+        if let Some(port) = self.config.port {
+            builder = builder.port(port);
+        }
+
+        if let Some(username) = &self.config.username {
+            let password = self.config.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+
+            let mechanisms = if self.config.auth_mechanisms.is_empty() {
+                vec![Mechanism::Plain, Mechanism::Login]
+            } else {
+                self.config
+                    .auth_mechanisms
+                    .iter()
+                    .map(|mechanism| match mechanism {
+                        SmtpAuthMechanism::Plain => Mechanism::Plain,
+                        SmtpAuthMechanism::Login => Mechanism::Login,
+                    })
+                    .collect()
+            };
+            builder = builder.authentication(mechanisms);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+impl MailTransport for SmtpTransport<'_> {
+    async fn prepare(&mut self) -> Result<(), KindleError> {
+        info!("Connecting to SMTP server {}...", self.config.host);
+
+        let mailer = self.build_mailer()?;
+        mailer.test_connection().await.map_err(|e| KindleError {
+            message: format!(
+                "Failed to connect to SMTP server {}: {}",
+                self.config.host, e
+            ),
+        })?;
+
+        self.mailer = Some(mailer);
+        Ok(())
+    }
+
+    async fn send(&self, mail: &OutgoingMail) -> Result<(), KindleError> {
+        let mailer = self.mailer.as_ref().ok_or_else(|| KindleError {
+            message: "SMTP transport used before connection".to_string(),
+        })?;
+
+        let message = MimeService::build_message(mail, &self.config.from)?;
+        mailer.send(message).await.map_err(|e| KindleError {
+            message: format!("Failed to send email: {}", e),
+        })?;
+
+        info!("Email with attachment sent successfully!");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::models::MailAttachment;
+
+    /// Start a local SMTP server accepting every connection
+    ///
+    /// The commands and message data it receives are appended to the returned
+    /// transcript. Recipients containing `rejected` are refused.
+    async fn start_server() -> (u16, Arc<Mutex<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let transcript = Arc::new(Mutex::new(String::new()));
+
+        let server_transcript = transcript.clone();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let transcript = server_transcript.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+
+                    let mut in_data = false;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        transcript.lock().unwrap().push_str(&format!("{}\n", line));
+                        let command = line.to_ascii_uppercase();
+                        let reply: &[u8] = if in_data {
+                            if line != "." {
+                                continue;
+                            }
+                            in_data = false;
+                            b"250 Queued\r\n"
+                        } else if command.starts_with("EHLO") {
+                            b"250-localhost\r\n250-AUTH PLAIN LOGIN\r\n250 8BITMIME\r\n"
+                        } else if command.starts_with("AUTH") {
+                            b"235 Authenticated\r\n"
+                        } else if command.starts_with("RCPT") && command.contains("REJECTED") {
+                            b"550 Mailbox unavailable\r\n"
+                        } else if command.starts_with("DATA") {
+                            in_data = true;
+                            b"354 Send data\r\n"
+                        } else if command.starts_with("QUIT") {
+                            writer.write_all(b"221 Bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            b"250 OK\r\n"
+                        };
+                        writer.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+
+        (port, transcript)
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            host: String::from("127.0.0.1"),
+            port: Some(port),
+            security: SmtpSecurity::None,
+            username: Some(String::from("user")),
+            password: Some(String::from("secret")),
+            auth_mechanisms: vec![SmtpAuthMechanism::Plain],
+            from: String::from("sender@example.com"),
+        }
+    }
+
+    fn mail(recipient: &str, path: &str) -> OutgoingMail {
+        OutgoingMail {
+            recipients: vec![recipient.to_string()],
+            subject: String::from("Books"),
+            body: String::from("Sent by kindle-sender"),
+            attachments: vec![MailAttachment {
+                path: path.to_string(),
+                name: String::from("book.epub"),
+                content_type: String::from("application/epub+zip"),
+                format: None,
+                size: 0,
+                sha256: String::new(),
+            }],
+            message_id: String::from("<run-1@kindle-sender.local>"),
+            headers: vec![(String::from("X-Kindle-Sender-Job"), String::from("run-1"))],
+        }
+    }
+
+    /// Write the attachment of the test emails
+    fn write_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "kindle-sender-smtp-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, b"book content").unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn send_delivers_message_to_server() {
+        let (port, transcript) = start_server().await;
+        let config = config(port);
+        let path = write_file("delivered.epub");
+        let mut transport = SmtpTransport::new(&config);
+
+        transport.prepare().await.unwrap();
+        transport.send(&mail("me@kindle.com", &path)).await.unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let transcript = transcript.lock().unwrap();
+        assert!(transcript.contains("AUTH PLAIN"));
+        assert!(transcript.contains("MAIL FROM:<sender@example.com>"));
+        assert!(transcript.contains("RCPT TO:<me@kindle.com>"));
+        assert!(transcript.contains("Message-ID: <run-1@kindle-sender.local>"));
+        assert!(transcript.contains("X-Kindle-Sender-Job: run-1"));
+        assert!(transcript.contains("filename=\"book.epub\""));
+    }
+
+    #[tokio::test]
+    async fn send_reports_rejected_recipient() {
+        let (port, _) = start_server().await;
+        let config = config(port);
+        let path = write_file("rejected.epub");
+        let mut transport = SmtpTransport::new(&config);
+
+        transport.prepare().await.unwrap();
+        let error = transport
+            .send(&mail("rejected@kindle.com", &path))
+            .await
+            .unwrap_err();
+        std::fs::remove_file(path).unwrap();
+
+        assert!(error.message.contains("Mailbox unavailable"));
+    }
+
+    #[tokio::test]
+    async fn send_requires_prepare() {
+        let config = config(25);
+        let transport = SmtpTransport::new(&config);
+
+        let error = transport
+            .send(&mail("me@kindle.com", "unused.epub"))
+            .await
+            .unwrap_err();
+        assert!(error.message.contains("before connection"));
+    }
+}
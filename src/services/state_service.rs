@@ -0,0 +1,110 @@
+//! # State Storage Service
+//!
+//! This module persists the JSON state files shared by the successive runs in the
+//! application directory.
+
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::models::KindleError;
+
+/// Name of the application directory in the home directory
+const APP_DIRECTORY_NAME: &str = ".kindle_sender";
+
+/// Service for reading and writing state files
+pub struct StateService {}
+
+impl StateService {
+    /// Get the path of a file in the application directory
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file
+    ///
+    /// # Returns
+    ///
+    /// * `PathBuf` - Path of the file in `~/.kindle_sender`
+    pub fn path(file_name: &str) -> PathBuf {
+        dirs::home_dir()
+            .unwrap()
+            .join(APP_DIRECTORY_NAME)
+            .join(file_name)
+    }
+
+    /// Read a state file, starting from the default state if there is none yet
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file in the application directory
+    /// * `description` - Description of the state, used in error messages
+    ///
+    /// # Returns
+    ///
+    /// * `Result<T, KindleError>` - The state or an error
+    pub fn read<T: DeserializeOwned + Default>(
+        file_name: &str,
+        description: &str,
+    ) -> Result<T, KindleError> {
+        let file_path = Self::path(file_name);
+        if !file_path.exists() {
+            return Ok(T::default());
+        }
+
+        let contents = fs::read_to_string(&file_path).map_err(|e| KindleError {
+            message: format!("Failed to read {}: {}", description, e),
+        })?;
+        serde_json::from_str(&contents).map_err(|e| KindleError {
+            message: format!(
+                "Failed to parse {} {}: {}",
+                description,
+                file_path.display(),
+                e
+            ),
+        })
+    }
+
+    /// Write a state file
+    ///
+    /// The state is written to a temporary file that then replaces the file, so that
+    /// an interrupted write never leaves a truncated file behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_name` - Name of the file in the application directory
+    /// * `description` - Description of the state, used in error messages
+    /// * `state` - The state to write
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), KindleError>` - Success or an error
+    pub fn write<T: Serialize>(
+        file_name: &str,
+        description: &str,
+        state: &T,
+    ) -> Result<(), KindleError> {
+        let file_path = Self::path(file_name);
+        if let Some(parent_dir) = file_path.parent() {
+            fs::create_dir_all(parent_dir).map_err(|e| KindleError {
+                message: format!("Failed to create application directory: {}", e),
+            })?;
+        }
+
+        let json = serde_json::to_string_pretty(state).map_err(|e| KindleError {
+            message: format!("Failed to serialize {}: {}", description, e),
+        })?;
+
+        let temp_path =
+            file_path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        fs::write(&temp_path, json)
+            .and_then(|_| fs::rename(&temp_path, &file_path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&temp_path);
+                KindleError {
+                    message: format!("Failed to write {}: {}", description, e),
+                }
+            })
+    }
+}
@@ -0,0 +1,336 @@
+//! # Attachment Streaming Service
+//!
+//! This module streams attachments from disk into HTTP request bodies, so that the
+//! memory used to send an e-book stays bounded whatever its size.
+
+use std::io::SeekFrom;
+
+use base64::{Engine as _, engine::general_purpose};
+use bytes::Bytes;
+use futures_util::stream::{self, BoxStream, StreamExt};
+use reqwest::Body;
+use serde::Serialize;
+use tokio::fs::File;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::models::{KindleError, MailAttachment};
+
+/// Number of file bytes encoded at once (a multiple of 3, so chunks need no padding)
+const ENCODE_CHUNK_SIZE: usize = 3 * 16 * 1024;
+
+/// Prefix of the placeholders replaced by the attachment contents in JSON payloads
+const PLACEHOLDER_PREFIX: &str = "@kindle-sender/attachment/";
+
+/// Part of a streamed request body
+#[derive(Clone)]
+enum BodyPart {
+    /// Bytes sent as is
+    Raw(Bytes),
+    /// File whose content is sent encoded in base64
+    Base64File(String),
+}
+
+/// Request body streamed from memory and files, which can be rebuilt for each attempt
+pub struct StreamedBody {
+    /// Parts of the body, in order
+    parts: Vec<BodyPart>,
+    /// Total length of the body in bytes
+    pub content_length: u64,
+}
+
+impl StreamedBody {
+    /// Build a new request body streaming the parts
+    ///
+    /// # Returns
+    ///
+    /// * `Body` - The request body, reading the files as it is sent
+    pub fn body(&self) -> Body {
+        let stream = stream::iter(self.parts.clone()).flat_map(
+            |part| -> BoxStream<'static, std::io::Result<Bytes>> {
+                match part {
+                    BodyPart::Raw(bytes) => stream::once(async move { Ok(bytes) }).boxed(),
+                    BodyPart::Base64File(path) => StreamService::base64_file_stream(path).boxed(),
+                }
+            },
+        );
+        Body::wrap_stream(stream)
+    }
+}
+
+/// Service for streaming attachments into request bodies
+pub struct StreamService {}
+
+impl StreamService {
+    /// Get the placeholder standing for the content of an attachment in a JSON payload
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - Index of the attachment in the list given to `json_body`
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The placeholder
+    pub fn placeholder(index: usize) -> String {
+        format!("{}{}", PLACEHOLDER_PREFIX, index)
+    }
+
+    /// Build a JSON body whose attachment placeholders are streamed as base64
+    ///
+    /// The payload is serialized with the placeholders returned by `placeholder`, then
+    /// each placeholder is replaced by the base64 content of the matching attachment,
+    /// read from disk while the request is sent.
+    ///
+    /// # Arguments
+    ///
+    /// * `payload` - The JSON payload containing the placeholders
+    /// * `attachments` - The attachments, in the order of their placeholders
+    ///
+    /// # Returns
+    ///
+    /// * `Result<StreamedBody, KindleError>` - The streamed body or an error
+    pub fn json_body<T: Serialize>(
+        payload: &T,
+        attachments: &[MailAttachment],
+    ) -> Result<StreamedBody, KindleError> {
+        let json = serde_json::to_string(payload).map_err(|e| KindleError {
+            message: format!("Failed to serialize request: {}", e),
+        })?;
+
+        let mut parts = Vec::new();
+        let mut content_length = 0;
+        let mut rest = json.as_str();
+
+        for (index, attachment) in attachments.iter().enumerate() {
+            let placeholder = Self::placeholder(index);
+            let position = rest.find(&placeholder).ok_or_else(|| KindleError {
+                message: format!("Missing content placeholder for {}", attachment.name),
+            })?;
+
+            parts.push(BodyPart::Raw(Bytes::copy_from_slice(
+                &rest.as_bytes()[..position],
+            )));
+            parts.push(BodyPart::Base64File(attachment.path.clone()));
+            content_length += position as u64 + attachment.size.div_ceil(3) * 4;
+
+            rest = &rest[position + placeholder.len()..];
+        }
+
+        parts.push(BodyPart::Raw(Bytes::copy_from_slice(rest.as_bytes())));
+        content_length += rest.len() as u64;
+
+        Ok(StreamedBody {
+            parts,
+            content_length,
+        })
+    }
+
+    /// Read a chunk of a file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file
+    /// * `offset` - Position of the chunk in the file
+    /// * `length` - Length of the chunk
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Bytes, KindleError>` - Content of the chunk or an error
+    pub async fn read_chunk(path: &str, offset: u64, length: usize) -> Result<Bytes, KindleError> {
+        let mut file = File::open(path).await.map_err(|e| KindleError {
+            message: format!("Failed to open file: {}", e),
+        })?;
+        file.seek(SeekFrom::Start(offset))
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to read file: {}", e),
+            })?;
+
+        let mut buffer = vec![0; length];
+        file.read_exact(&mut buffer)
+            .await
+            .map_err(|e| KindleError {
+                message: format!("Failed to read file: {}", e),
+            })?;
+
+        Ok(Bytes::from(buffer))
+    }
+
+    /// Stream the content of a file encoded in base64
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the file
+    ///
+    /// # Returns
+    ///
+    /// * `impl Stream` - Base64 chunks of the file
+    fn base64_file_stream(
+        path: String,
+    ) -> impl futures_util::Stream<Item = std::io::Result<Bytes>> + Send + 'static {
+        stream::try_unfold(None, move |file: Option<File>| {
+            let path = path.clone();
+            async move {
+                let mut file = match file {
+                    Some(file) => file,
+                    None => File::open(&path).await?,
+                };
+
+                // Fill the whole buffer unless the end of the file is reached, so
+                // that only the last chunk may need base64 padding
+                let mut buffer = vec![0; ENCODE_CHUNK_SIZE];
+                let mut filled = 0;
+                while filled < buffer.len() {
+                    let read = file.read(&mut buffer[filled..]).await?;
+                    if read == 0 {
+                        break;
+                    }
+                    filled += read;
+                }
+
+                if filled == 0 {
+                    return Ok(None);
+                }
+
+                buffer.truncate(filled);
+                let encoded = general_purpose::STANDARD.encode(&buffer);
+                Ok(Some((Bytes::from(encoded), Some(file))))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    /// Write a test file whose bytes depend on their position
+    fn write_file(name: &str, size: usize) -> (PathBuf, Vec<u8>) {
+        let path = std::env::temp_dir().join(format!(
+            "kindle-sender-stream-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let content: Vec<u8> = (0..size).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+        (path, content)
+    }
+
+    fn attachment(path: &Path, name: &str, size: usize) -> MailAttachment {
+        MailAttachment {
+            path: path.to_string_lossy().to_string(),
+            name: name.to_string(),
+            content_type: "application/octet-stream".to_string(),
+            format: None,
+            size: size as u64,
+            sha256: String::new(),
+        }
+    }
+
+    /// Read a streamed body the way it is sent
+    async fn read_body(body: &StreamedBody) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for part in &body.parts {
+            match part {
+                BodyPart::Raw(raw) => bytes.extend_from_slice(raw),
+                BodyPart::Base64File(path) => {
+                    let mut stream = Box::pin(StreamService::base64_file_stream(path.clone()));
+                    while let Some(chunk) = stream.next().await {
+                        bytes.extend_from_slice(&chunk.unwrap());
+                    }
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Build the body of files of the given sizes and check every attachment in it
+    async fn check_body(prefix: &str, sizes: &[usize]) {
+        let files: Vec<_> = sizes
+            .iter()
+            .enumerate()
+            .map(|(index, size)| {
+                let name = format!("{}-{}.bin", prefix, index);
+                let (path, content) = write_file(&name, *size);
+                (attachment(&path, &name, *size), path, content)
+            })
+            .collect();
+        let attachments: Vec<_> = files.iter().map(|(a, _, _)| a.clone()).collect();
+        let payload = json!({
+            "message": {
+                "subject": "Test",
+                "attachments": (0..attachments.len())
+                    .map(|index| json!({
+                        "name": attachments[index].name,
+                        "contentBytes": StreamService::placeholder(index),
+                    }))
+                    .collect::<Vec<_>>(),
+            }
+        });
+
+        let body = StreamService::json_body(&payload, &attachments).unwrap();
+        let bytes = read_body(&body).await;
+        for (_, path, _) in &files {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        assert_eq!(body.content_length, bytes.len() as u64);
+        let value: Value = serde_json::from_slice(&bytes).unwrap();
+        for (index, (attachment, _, content)) in files.iter().enumerate() {
+            let sent = &value["message"]["attachments"][index];
+            assert_eq!(sent["name"], attachment.name.as_str());
+            let decoded = general_purpose::STANDARD
+                .decode(sent["contentBytes"].as_str().unwrap())
+                .unwrap();
+            assert_eq!(&decoded, content, "content of attachment {}", index);
+        }
+    }
+
+    #[tokio::test]
+    async fn json_body_streams_single_attachment() {
+        check_body("single", &[1000]).await;
+    }
+
+    #[tokio::test]
+    async fn json_body_streams_multiple_attachments() {
+        check_body("multiple", &[10, 2000, 30]).await;
+    }
+
+    #[tokio::test]
+    async fn json_body_length_covers_base64_padding() {
+        for size in [0, 1, 2, 3, 4, 5] {
+            check_body(&format!("padding{}", size), &[size]).await;
+        }
+        // Sizes around the encoding chunk, whose last chunk alone may be padded
+        check_body(
+            "chunks",
+            &[
+                ENCODE_CHUNK_SIZE - 1,
+                ENCODE_CHUNK_SIZE,
+                ENCODE_CHUNK_SIZE + 1,
+                ENCODE_CHUNK_SIZE * 2 + 2,
+            ],
+        )
+        .await;
+    }
+
+    #[tokio::test]
+    async fn json_body_distinguishes_placeholders_sharing_a_prefix() {
+        // Placeholder 1 is a prefix of placeholders 10 and 11
+        let sizes: Vec<usize> = (0..12).map(|index| index * 5 + 1).collect();
+        check_body("prefix", &sizes).await;
+    }
+
+    #[test]
+    fn json_body_reports_missing_placeholder() {
+        let (path, _) = write_file("missing.bin", 3);
+        let attachments = [attachment(&path, "missing.bin", 3)];
+        let result = StreamService::json_body(&json!({ "message": {} }), &attachments);
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(result.is_err());
+    }
+}
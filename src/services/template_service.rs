@@ -0,0 +1,50 @@
+//! # Template Service
+//!
+//! This module renders the subject and body templates of the emails.
+
+use std::collections::BTreeMap;
+
+/// Service for rendering templates with `{name}` placeholders
+pub struct TemplateService {}
+
+impl TemplateService {
+    /// Render a template
+    ///
+    /// Each `{name}` placeholder is replaced by the value of `name`. Placeholders
+    /// without a value are kept as is, and values are not rendered again.
+    ///
+    /// # Arguments
+    ///
+    /// * `template` - The template
+    /// * `values` - The value of each placeholder, by name
+    ///
+    /// # Returns
+    ///
+    /// * `String` - The rendered text
+    pub fn render(template: &str, values: &BTreeMap<&str, String>) -> String {
+        let mut rendered = String::with_capacity(template.len());
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            let value = rest
+                .find('}')
+                .and_then(|end| values.get(&rest[1..end]).map(|value| (end, value)));
+            match value {
+                Some((end, value)) => {
+                    rendered.push_str(value);
+                    rest = &rest[end + 1..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+
+        rendered.push_str(rest);
+        rendered
+    }
+}
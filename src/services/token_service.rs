@@ -0,0 +1,60 @@
+//! # Token Storage Service
+//!
+//! This module provides services for caching OAuth tokens on disk between runs.
+
+use std::error::Error;
+use std::fs;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::models::TokenResponse;
+
+/// Service for reading and writing cached OAuth tokens
+pub struct TokenService {}
+
+impl TokenService {
+    /// Read a token from a file
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to the file containing the token
+    ///
+    /// # Returns
+    ///
+    /// * `Result<TokenResponse, Box<dyn Error>>` - Token response or an error
+    pub fn read_token_from_file(file_path: &PathBuf) -> Result<TokenResponse, Box<dyn Error>> {
+        if file_path.exists() {
+            let mut file = File::open(file_path)?;
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            let token_response: TokenResponse = serde_json::from_str(&contents)?;
+            Ok(token_response)
+        } else {
+            Err("File not found".into())
+        }
+    }
+
+    /// Write a token to a file
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path to write the token to
+    /// * `token_response` - Token response to write
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(), Box<dyn Error>>` - Success or an error
+    pub fn write_token_to_file(
+        file_path: &PathBuf,
+        token_response: &TokenResponse,
+    ) -> Result<(), Box<dyn Error>> {
+        if let Some(parent_dir) = file_path.parent() {
+            fs::create_dir_all(parent_dir)?;
+        }
+        let mut file = File::create(file_path)?;
+        let json = serde_json::to_string(token_response)?;
+        file.write_all(json.as_bytes())?;
+        Ok(())
+    }
+}